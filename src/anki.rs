@@ -0,0 +1,127 @@
+use std::io::{self, Write};
+
+use crate::{index, markup::escape, naming, vocabulary::VocabularyEntry};
+
+pub const DEFAULT_DECK: &str = "BBC Learning English";
+
+/// A Basic note for one vocabulary term.
+#[derive(Debug, Clone)]
+pub struct Note {
+    /// Stable across exports, so Anki updates the note on re-import.
+    pub guid: String,
+    pub front: String,
+    /// HTML: the definition, then the example sentence and the episode
+    /// audio.
+    pub back: String,
+    pub tags: Vec<String>,
+}
+
+impl Note {
+    pub fn new(
+        series: &str,
+        entry: &VocabularyEntry,
+        example: Option<&str>,
+        audio_file: Option<&str>,
+    ) -> Self {
+        let mut back = escape(&entry.definition);
+        if let Some(example) = example {
+            back.push_str(&format!("<br><br><i>{}</i>", escape(example)));
+        }
+        if let Some(file) = audio_file {
+            back.push_str(&format!("<br><br>[sound:{}]", file));
+        }
+
+        let series = naming::sanitize(series);
+        Self {
+            guid: guid(entry),
+            front: escape(&entry.term),
+            back,
+            tags: vec![series.clone(), format!("{}::{}", series, entry.episode_pid)],
+        }
+    }
+}
+
+/// Derived from the episode and the term, ignoring case.
+fn guid(entry: &VocabularyEntry) -> String {
+    let key = format!("{}\t{}", entry.episode_pid, entry.term.to_lowercase());
+    format!("bbc-{}", &index::sha256_hex(key.as_bytes())[..16])
+}
+
+/// Writes `notes` as a tab-separated file with the header lines Anki 2.1.55
+/// and later read to pick the deck, note type, GUID and tag columns.
+pub fn write_tsv(out: &mut impl Write, deck: &str, notes: &[Note]) -> io::Result<()> {
+    writeln!(out, "#separator:tab")?;
+    writeln!(out, "#html:true")?;
+    writeln!(out, "#notetype:Basic")?;
+    writeln!(out, "#deck:{}", field(deck))?;
+    writeln!(out, "#guid column:1")?;
+    writeln!(out, "#tags column:4")?;
+    for note in notes {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            note.guid,
+            field(&note.front),
+            field(&note.back),
+            field(&note.tags.join(" "))
+        )?;
+    }
+    Ok(())
+}
+
+/// Keeps tabs and line breaks from splitting a field.
+fn field(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(term: &str, definition: &str) -> VocabularyEntry {
+        VocabularyEntry {
+            term: term.to_string(),
+            definition: definition.to_string(),
+            episode_pid: "p0l4xq77".to_string(),
+        }
+    }
+
+    #[test]
+    fn guid_is_stable() {
+        let note = Note::new(
+            "6 Minute English",
+            &entry("policed", "controlled by the police"),
+            None,
+            None,
+        );
+        // Changing this breaks re-imports of earlier exports.
+        assert_eq!(note.guid, "bbc-5a1a19cff2a74870");
+
+        let edited = Note::new(
+            "6 Minute English",
+            &entry("Policed", "watched over by the police"),
+            Some("The streets are policed at night."),
+            Some("Can_AI_solve_crime_-_p0l4xq77.mp3"),
+        );
+        assert_eq!(edited.guid, note.guid);
+    }
+
+    #[test]
+    fn back_plays_the_local_audio_file() {
+        let note = Note::new(
+            "6 Minute English",
+            &entry("policed", "controlled by the police"),
+            Some("The streets are policed at night."),
+            Some("Can_AI_solve_crime_-_p0l4xq77.mp3"),
+        );
+        assert_eq!(
+            note.back,
+            "controlled by the police<br><br><i>The streets are policed at night.</i>\
+             <br><br>[sound:Can_AI_solve_crime_-_p0l4xq77.mp3]"
+        );
+        assert_eq!(
+            note.tags,
+            ["6_Minute_English", "6_Minute_English::p0l4xq77"]
+        );
+    }
+}
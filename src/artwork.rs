@@ -0,0 +1,162 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use reqwest::Client;
+use scraper::{ElementRef, Html, Selector};
+
+use crate::{episode, mime};
+
+const CACHE_DIR: &str = ".artwork";
+/// Cache name of the series image, which has no stable id of its own.
+const SERIES_IMAGE: &str = "series";
+/// Size requested from BBC image URLs that leave it as a `{recipe}`
+/// placeholder.
+const RECIPE: &str = "640x640";
+
+/// Cover art held in memory, ready to embed.
+#[derive(Debug, Clone)]
+pub struct Artwork {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Downloaded images, kept in `<download_folder>/.artwork` so each one is
+/// fetched only once.
+#[derive(Debug, Clone)]
+pub struct ArtworkCache {
+    dir: PathBuf,
+}
+
+impl ArtworkCache {
+    pub fn new(download_folder: &Path) -> Self {
+        Self {
+            dir: download_folder.join(CACHE_DIR),
+        }
+    }
+
+    /// The image of an episode, named after the last segment of its URL.
+    pub async fn episode(&self, client: &Client, url: &str) -> io::Result<Artwork> {
+        let name = url
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| io::Error::other(format!("no file name in {}", url)))?;
+        self.get(client, url, name).await
+    }
+
+    /// The series image, downloaded from `url` unless a copy is cached.
+    pub async fn series(&self, client: &Client, url: Option<&str>) -> io::Result<Artwork> {
+        if let Some(path) = self.find(SERIES_IMAGE).await {
+            return read(&path).await;
+        }
+        let url = url.ok_or_else(|| io::Error::other("no series image known"))?;
+        let name = format!("{}.{}", SERIES_IMAGE, extension(url));
+        self.get(client, url, &name).await
+    }
+
+    async fn get(&self, client: &Client, url: &str, name: &str) -> io::Result<Artwork> {
+        let path = self.dir.join(name);
+        if tokio::fs::try_exists(&path).await? {
+            return read(&path).await;
+        }
+
+        let bytes = client
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(io::Error::other)?
+            .bytes()
+            .await
+            .map_err(io::Error::other)?;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&path, &bytes).await?;
+        Ok(Artwork {
+            mime_type: mime_type(name).to_string(),
+            data: bytes.to_vec(),
+        })
+    }
+
+    /// The cached file whose stem is `stem`, whatever its extension.
+    async fn find(&self, stem: &str) -> Option<PathBuf> {
+        let mut entries = tokio::fs::read_dir(&self.dir).await.ok()?;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.file_stem().is_some_and(|s| s == stem) {
+                return Some(path);
+            }
+        }
+        None
+    }
+}
+
+/// The cached series image, relative to the download folder, if a
+/// download has fetched one.
+pub fn cached_series_image(download_folder: &Path) -> Option<String> {
+    std::fs::read_dir(download_folder.join(CACHE_DIR))
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .find(|name| name.split('.').next() == Some(SERIES_IMAGE))
+        .map(|name| format!("{}/{}", CACHE_DIR, name))
+}
+
+async fn read(path: &Path) -> io::Result<Artwork> {
+    Ok(Artwork {
+        mime_type: mime_type(&path.to_string_lossy()).to_string(),
+        data: tokio::fs::read(path).await?,
+    })
+}
+
+fn extension(url: &str) -> &str {
+    url.rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map_or("jpg", |(_, ext)| ext)
+}
+
+/// The image type of `name`; images saved without a known extension are
+/// taken for JPEGs.
+fn mime_type(name: &str) -> &'static str {
+    Some(mime::of(name))
+        .filter(|mime| mime.starts_with("image/"))
+        .unwrap_or("image/jpeg")
+}
+
+/// The programme image of the page, from its `og:image` meta tag.
+pub fn series_image_url(document: &Html) -> Option<String> {
+    let selector = Selector::parse("meta[property=\"og:image\"]").ok()?;
+    document
+        .select(&selector)
+        .next()?
+        .value()
+        .attr("content")
+        .map(expand_recipe)
+}
+
+/// The first image matching `selector` in an episode card.
+pub fn card_image_url(card: ElementRef, selector: &str) -> Option<String> {
+    let img = Selector::parse(selector).ok()?;
+    card.select(&img).find_map(image_source).map(expand_recipe)
+}
+
+/// The URL of an `img`, looking at the lazy-loading attributes BBC uses.
+fn image_source<'a>(img: ElementRef<'a>) -> Option<&'a str> {
+    let element = img.value();
+    element
+        .attr("data-src")
+        .or_else(|| element.attr("src"))
+        .or_else(|| {
+            element
+                .attr("data-srcset")
+                .or_else(|| element.attr("srcset"))
+                .and_then(|srcset| srcset.split_whitespace().next())
+        })
+        .filter(|url| url.starts_with("http") || url.starts_with("//"))
+}
+
+fn expand_recipe(url: &str) -> String {
+    episode::absolute(&url.replace("{recipe}", RECIPE))
+}
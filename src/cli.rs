@@ -0,0 +1,194 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+
+use crate::{anki, schedule::Schedule};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Download BBC Learning English podcasts")]
+pub struct Cli {
+    /// Series config file [default: $XDG_CONFIG_HOME/bbc-scraper/podcasts.toml]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Log more: -v for debug messages, -vv for trace
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Log less and hide progress bars: -q for warnings and errors, -qq for errors only
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Log filter in RUST_LOG syntax, e.g. `bbc_scraper=debug` [default: $RUST_LOG, else from -v/-q]
+    #[arg(long, global = true, value_name = "FILTER")]
+    pub log_filter: Option<String>,
+
+    /// Also write the log to this file, one JSON object per line
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Download new episodes
+    Sync {
+        /// Series names to sync [default: all configured series]
+        series: Vec<String>,
+
+        /// Listing pages to walk, overriding `max_pages` (0 = all), even past indexed ones
+        #[arg(long, value_name = "N", conflicts_with = "full_archive")]
+        pages: Option<usize>,
+
+        /// Walk every listing page to back-fill the whole archive
+        #[arg(long)]
+        full_archive: bool,
+
+        /// Also try episodes that failed permanently on an earlier run
+        #[arg(long)]
+        retry_failed: bool,
+
+        /// Write a summary of the run in this format
+        #[arg(long, value_enum, value_name = "FORMAT")]
+        report: Option<ReportFormat>,
+
+        /// File to write the report to [default: stdout]
+        #[arg(long, value_name = "PATH", requires = "report")]
+        report_file: Option<PathBuf>,
+    },
+    /// Keep syncing each series on its schedule until stopped
+    Watch {
+        /// Series names to watch [default: all configured series]
+        series: Vec<String>,
+
+        /// Check every series this often, e.g. `30m`, overriding `interval` and `schedule`
+        #[arg(long, value_name = "DURATION", value_parser = Schedule::interval, conflicts_with = "schedule")]
+        interval: Option<Schedule>,
+
+        /// Check every series when this cron expression matches, e.g. `0 7 * * *`
+        #[arg(long, value_name = "CRON", value_parser = Schedule::cron)]
+        schedule: Option<Schedule>,
+
+        /// Also try episodes that failed permanently on an earlier check
+        #[arg(long)]
+        retry_failed: bool,
+    },
+    /// Show the episodes on the remote page and mark the local ones
+    List {
+        /// Series names to list [default: all configured series]
+        series: Vec<String>,
+    },
+    /// Show per-series counts and the last sync time
+    Status,
+    /// Check local files against the index
+    Verify,
+    /// Rewrite the ID3 tags of every downloaded episode
+    Retag {
+        /// Series names to retag [default: all configured series]
+        series: Vec<String>,
+    },
+    /// Save the transcripts of downloaded episodes that have none yet
+    Transcripts {
+        /// Series names to fetch transcripts for [default: all configured series]
+        series: Vec<String>,
+
+        /// Fetch every transcript again, replacing the saved ones
+        #[arg(long)]
+        force: bool,
+    },
+    /// Write library data in other formats
+    Export {
+        #[command(subcommand)]
+        what: Export,
+    },
+    /// Write an RSS feed of the downloaded episodes into each series folder
+    Feed {
+        /// Series names to write feeds for [default: all configured series]
+        series: Vec<String>,
+
+        /// URL the download folders are served under, overriding `base_url`
+        #[arg(long, value_name = "URL")]
+        base_url: Option<String>,
+    },
+    /// Serve the feeds, episodes and transcripts over HTTP
+    Serve {
+        /// Series names to serve [default: all configured series]
+        series: Vec<String>,
+
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+
+        /// URL the server is reached at, for links in feeds [default: `base_url`, else the request's Host]
+        #[arg(long, value_name = "URL")]
+        base_url: Option<String>,
+    },
+    /// Show what each selector matches on a listing page
+    Probe {
+        /// Series names to probe [default: all configured series]
+        series: Vec<String>,
+
+        /// Read the page from a saved HTML or feed file instead of fetching it
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+
+        /// Listing page to fetch
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        page: usize,
+    },
+    /// Rename indexed files to match the current filename template
+    Rename {
+        /// Series names to rename [default: all configured series]
+        series: Vec<String>,
+
+        /// Move the files instead of only showing the plan
+        #[arg(long)]
+        apply: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Export {
+    /// Vocabulary terms from the saved transcripts
+    Vocabulary {
+        /// Series names to export [default: all configured series]
+        series: Vec<String>,
+
+        #[arg(long, value_enum, default_value_t = VocabularyFormat::Json)]
+        format: VocabularyFormat,
+
+        /// File to write [default: stdout]
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Vocabulary as Anki notes, in a TSV file Anki can import
+    Anki {
+        /// Series names to export [default: all configured series]
+        series: Vec<String>,
+
+        /// File to write [default: stdout]
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Deck to import the notes into
+        #[arg(long, default_value = anki::DEFAULT_DECK)]
+        deck: String,
+
+        /// Play the episode's local audio file on the back of each note
+        #[arg(long)]
+        audio: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum VocabularyFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Json,
+}
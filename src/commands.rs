@@ -0,0 +1,948 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    net::SocketAddr,
+    path::Path,
+    process::ExitCode,
+    sync::Arc,
+};
+
+use chrono::Local;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+use crate::{
+    anki::{self, Note},
+    cli::{ReportFormat, VocabularyFormat},
+    config::{Config, Limits, PodcastConfig},
+    downloader::{PodcastDownloader, SyncOptions},
+    episode::{self, Variant},
+    feed::{self, FEED_FILE_NAME},
+    fetch,
+    index::{self, EpisodeRecord},
+    limiter::Limiter,
+    naming,
+    progress::Progress,
+    report::{self, RunReport, SeriesReport},
+    selectors::Selectors,
+    server,
+    source::{self, SourceKind},
+    vocabulary::{self, VocabularyEntry},
+    watch,
+};
+
+/// Exit code when a run found nothing to download.
+pub const EXIT_NOTHING_NEW: u8 = 3;
+/// Exit code when some downloads or checks failed.
+pub const EXIT_PARTIAL: u8 = 4;
+
+/// How a command ended, when it did not hit a fatal error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    Success,
+    NothingNew,
+    Partial,
+}
+
+impl From<Outcome> for ExitCode {
+    fn from(outcome: Outcome) -> Self {
+        match outcome {
+            Outcome::Success => ExitCode::SUCCESS,
+            Outcome::NothingNew => ExitCode::from(EXIT_NOTHING_NEW),
+            Outcome::Partial => ExitCode::from(EXIT_PARTIAL),
+        }
+    }
+}
+
+/// The configured series whose names match `names` (case-insensitive),
+/// or all of them when `names` is empty.
+pub fn select_series(config: Config, names: &[String]) -> io::Result<Vec<PodcastConfig>> {
+    if names.is_empty() {
+        return Ok(config.podcasts);
+    }
+
+    if let Some(unknown) = names.iter().find(|name| {
+        !config
+            .podcasts
+            .iter()
+            .any(|podcast| podcast.name.eq_ignore_ascii_case(name))
+    }) {
+        let known = config
+            .podcasts
+            .iter()
+            .map(|podcast| podcast.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Unknown series '{}' (configured: {})", unknown, known),
+        ));
+    }
+
+    Ok(config
+        .podcasts
+        .into_iter()
+        .filter(|podcast| {
+            names
+                .iter()
+                .any(|name| podcast.name.eq_ignore_ascii_case(name))
+        })
+        .collect())
+}
+
+/// Syncs every series at once, each in its own task, with downloads
+/// sharing the slots of `limits` and showing their bars on `progress`.
+/// With `report`, a summary of the run is written to `report_file` or
+/// stdout.
+pub async fn sync(
+    podcasts: Vec<PodcastConfig>,
+    limits: &Limits,
+    options: SyncOptions,
+    progress: Progress,
+    report: Option<ReportFormat>,
+    report_file: Option<&Path>,
+) -> io::Result<Outcome> {
+    let started = Local::now();
+    let mut series = Vec::new();
+    let mut found = 0;
+    let mut completed = 0;
+    let mut failed = 0;
+
+    info!("BBC Scraper {}", env!("CARGO_PKG_VERSION"));
+
+    let limiter = Arc::new(Limiter::new(limits));
+    let mut tasks = JoinSet::new();
+    for (position, podcast) in podcasts.into_iter().enumerate() {
+        let name = podcast.name.clone();
+        match PodcastDownloader::with_limiter(podcast, Arc::clone(&limiter)) {
+            Ok(downloader) => {
+                info!("Starting downloader for {}...", name);
+                let downloader = downloader.with_progress(progress.clone());
+                tasks.spawn(async move {
+                    let result = downloader.download_episodes(options).await;
+                    (position, name, result)
+                });
+            }
+            Err(e) => {
+                error!("Failed to initialize {} downloader: {}", name, e);
+                failed += 1;
+                series.push((position, SeriesReport::failed(&name, &e)));
+            }
+        }
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        let (position, name, result) = joined.map_err(io::Error::other)?;
+        match result {
+            Ok(summary) => {
+                found += summary.downloads.len();
+                completed += summary.downloaded;
+                failed += summary.failed;
+                series.push((position, SeriesReport::new(&name, summary)));
+            }
+            Err(e) => {
+                error!("Error downloading {}: {}", name, e);
+                failed += 1;
+                series.push((position, SeriesReport::failed(&name, &e)));
+            }
+        }
+        info!("Finished {}", name);
+    }
+
+    info!(
+        "All podcast downloads completed: {} downloaded, {} failed",
+        completed, failed
+    );
+
+    let outcome = if failed > 0 {
+        Outcome::Partial
+    } else if found == 0 {
+        Outcome::NothingNew
+    } else {
+        Outcome::Success
+    };
+
+    if let Some(format) = report {
+        series.sort_by_key(|(position, _)| *position);
+        let run = RunReport {
+            status: outcome,
+            started,
+            finished: Local::now(),
+            series: series.into_iter().map(|(_, series)| series).collect(),
+        };
+        let mut out = open_output(report_file)?;
+        match format {
+            ReportFormat::Json => report::write_json(&mut out, &run)?,
+        }
+        out.flush()?;
+    }
+
+    Ok(outcome)
+}
+
+pub async fn list(podcasts: Vec<PodcastConfig>) -> io::Result<Outcome> {
+    let mut failed = false;
+
+    for podcast in podcasts {
+        let downloader = PodcastDownloader::new(podcast)?;
+        println!("{}:", downloader.config().name);
+
+        match downloader.fetch_episodes().await {
+            Ok(episodes) => {
+                for episode in episodes {
+                    let marker = if downloader.is_already_downloaded(&episode) {
+                        "x"
+                    } else if downloader.is_known(&episode) {
+                        "!"
+                    } else {
+                        " "
+                    };
+                    let date = episode
+                        .broadcast_date
+                        .map_or_else(|| "----------".to_string(), |date| date.to_string());
+                    let duration = episode
+                        .duration
+                        .map(|duration| format!(" ({} min)", duration.as_secs().div_ceil(60)))
+                        .unwrap_or_default();
+                    let variant = match episode.variant {
+                        Variant::High => "",
+                        Variant::Low => " [low]",
+                    };
+                    println!(
+                        "  [{}] {} {}{}{}",
+                        marker, date, episode.title, duration, variant
+                    );
+                }
+            }
+            Err(e) => {
+                error!(series = %downloader.config().name, "Failed to list episodes: {}", e);
+                failed = true;
+            }
+        }
+    }
+
+    Ok(if failed {
+        Outcome::Partial
+    } else {
+        Outcome::Success
+    })
+}
+
+pub fn status(podcasts: Vec<PodcastConfig>) -> io::Result<Outcome> {
+    for podcast in podcasts {
+        let downloader = PodcastDownloader::new(podcast)?;
+        let index = downloader.index();
+        let local = downloader.local_files()?;
+        let last_sync = index
+            .last_download()
+            .map(|timestamp| timestamp.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "never".to_string());
+
+        println!("{}", downloader.config().name);
+        println!(
+            "  folder:     {}",
+            downloader.config().download_folder.display()
+        );
+        println!("  indexed:    {}", index.downloaded().count());
+        println!("  failed:     {}", index.failed().count());
+        println!("  local:      {}", local.len());
+        println!("  last sync:  {}", last_sync);
+    }
+
+    Ok(Outcome::Success)
+}
+
+pub fn verify(podcasts: Vec<PodcastConfig>) -> io::Result<Outcome> {
+    let mut problems = 0;
+
+    for podcast in podcasts {
+        let downloader = PodcastDownloader::new(podcast)?;
+        println!("{}:", downloader.config().name);
+
+        let verification = downloader.verify()?;
+        for (filename, problem) in &verification.problems {
+            println!("  {}: {}", filename, problem);
+        }
+        if verification.unadopted > 0 {
+            // Migrated entries whose files `sync` has not matched yet;
+            // the untracked files are most likely theirs.
+            for name in &verification.untracked {
+                println!("  unadopted: {}", name);
+            }
+            println!(
+                "  {} entries unadopted; run `sync --full-archive` to adopt their files",
+                verification.unadopted
+            );
+        } else {
+            for name in &verification.untracked {
+                println!("  not in index: {}", name);
+            }
+        }
+        if !verification.is_clean() {
+            problems += verification.problems.len();
+            if verification.unadopted == 0 {
+                problems += verification.untracked.len();
+            }
+        }
+    }
+
+    if problems > 0 {
+        println!("{} problems found", problems);
+        Ok(Outcome::Partial)
+    } else {
+        println!("All indexed files match");
+        Ok(Outcome::Success)
+    }
+}
+
+pub async fn retag(podcasts: Vec<PodcastConfig>) -> io::Result<Outcome> {
+    let mut failed = 0;
+
+    for podcast in podcasts {
+        let downloader = PodcastDownloader::new(podcast)?;
+        println!("{}:", downloader.config().name);
+
+        let records = downloader
+            .index()
+            .downloaded()
+            .filter(|record| record.filename.is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut tagged = Vec::new();
+        for mut record in records {
+            let filename = record.filename.clone().unwrap_or_default();
+            match downloader.tag_episode(&mut record).await {
+                Ok(true) => tagged.push(record),
+                Ok(false) => {}
+                Err(e) => {
+                    error!(
+                        series = %downloader.config().name,
+                        vpid = %record.vpid,
+                        "Failed to tag {}: {}",
+                        filename,
+                        e
+                    );
+                    failed += 1;
+                }
+            }
+        }
+
+        let count = tagged.len();
+        let mut index = downloader.index();
+        index.update(tagged);
+        index.compact()?;
+        println!("  tagged {} files", count);
+    }
+
+    Ok(if failed > 0 {
+        Outcome::Partial
+    } else {
+        Outcome::Success
+    })
+}
+
+/// Saves the transcripts of downloaded episodes that have none yet, or
+/// of every downloaded episode with `force`.
+pub async fn transcripts(podcasts: Vec<PodcastConfig>, force: bool) -> io::Result<Outcome> {
+    let mut failed = 0;
+
+    for podcast in podcasts {
+        let downloader = PodcastDownloader::new(podcast)?;
+        println!("{}:", downloader.config().name);
+
+        let records = downloader
+            .index()
+            .episodes()
+            .filter(|record| force || record.transcript.is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut saved = Vec::new();
+        let mut missing = 0;
+        for mut record in records {
+            let name = record.title.clone().unwrap_or_else(|| record.vpid.clone());
+            match downloader.save_transcript(&mut record).await {
+                Ok(true) => saved.push(record),
+                Ok(false) => missing += 1,
+                Err(e) => {
+                    error!(
+                        series = %downloader.config().name,
+                        vpid = %record.vpid,
+                        "Failed to save the transcript of {}: {}",
+                        name,
+                        e
+                    );
+                    failed += 1;
+                }
+            }
+        }
+
+        let count = saved.len();
+        let mut index = downloader.index();
+        // The other variant of an episode shares its transcript.
+        let siblings = saved
+            .iter()
+            .flat_map(|record| {
+                index
+                    .variants(&record.vpid)
+                    .filter(|other| other.variant() != record.variant())
+                    .map(|other| {
+                        let mut other = other.clone();
+                        other.copy_transcript(record);
+                        other
+                    })
+            })
+            .collect::<Vec<_>>();
+        index.update(saved);
+        index.update(siblings);
+        index.compact()?;
+        println!("  saved {} transcripts", count);
+        if missing > 0 {
+            println!("  {} episodes have no transcript page", missing);
+        }
+    }
+
+    Ok(if failed > 0 {
+        Outcome::Partial
+    } else {
+        Outcome::Success
+    })
+}
+
+/// Writes the vocabulary of every indexed episode, oldest first, to
+/// `output` or stdout.
+pub fn export_vocabulary(
+    podcasts: Vec<PodcastConfig>,
+    format: VocabularyFormat,
+    output: Option<&Path>,
+) -> io::Result<Outcome> {
+    let mut entries = Vec::<VocabularyEntry>::new();
+    for podcast in podcasts {
+        let downloader = PodcastDownloader::new(podcast)?;
+        let index = downloader.index();
+        let mut records = index.episodes().collect::<Vec<_>>();
+        records.sort_by_key(|record| record.publish_order());
+        entries.extend(
+            records
+                .into_iter()
+                .flat_map(|record| record.vocabulary.clone()),
+        );
+    }
+
+    let mut out = open_output(output)?;
+    match format {
+        VocabularyFormat::Json => vocabulary::write_json(&mut out, &entries)?,
+        VocabularyFormat::Csv => vocabulary::write_csv(&mut out, &entries)?,
+    }
+    out.flush()?;
+
+    if let Some(path) = output {
+        println!("Wrote {} terms to {}", entries.len(), path.display());
+    }
+    Ok(if entries.is_empty() {
+        Outcome::NothingNew
+    } else {
+        Outcome::Success
+    })
+}
+
+/// Writes one Anki note per vocabulary term, oldest episode first, with an
+/// example sentence from the saved transcript when one uses the term.
+pub fn export_anki(
+    podcasts: Vec<PodcastConfig>,
+    output: Option<&Path>,
+    deck: &str,
+    audio: bool,
+) -> io::Result<Outcome> {
+    let mut notes = Vec::new();
+    for podcast in podcasts {
+        let downloader = PodcastDownloader::new(podcast)?;
+        let folder = &downloader.config().download_folder;
+        let index = downloader.index();
+        let mut records = index
+            .episodes()
+            .filter(|record| !record.vocabulary.is_empty())
+            .collect::<Vec<_>>();
+        records.sort_by_key(|record| record.publish_order());
+
+        for record in records {
+            let transcript = record
+                .transcript
+                .as_ref()
+                .and_then(|path| fs::read_to_string(folder.join(path)).ok())
+                .unwrap_or_default();
+            // Anki finds `[sound:]` files by name in its media folder.
+            let audio_file = record
+                .filename
+                .as_deref()
+                .filter(|_| audio)
+                .map(|name| name.rsplit('/').next().unwrap_or(name));
+            for entry in &record.vocabulary {
+                let example = vocabulary::example_sentence(&transcript, &entry.term);
+                notes.push(Note::new(
+                    &record.series,
+                    entry,
+                    example.as_deref(),
+                    audio_file,
+                ));
+            }
+        }
+    }
+
+    let mut out = open_output(output)?;
+    anki::write_tsv(&mut out, deck, &notes)?;
+    out.flush()?;
+
+    if let Some(path) = output {
+        println!("Wrote {} notes to {}", notes.len(), path.display());
+    }
+    Ok(if notes.is_empty() {
+        Outcome::NothingNew
+    } else {
+        Outcome::Success
+    })
+}
+
+/// A buffered writer to `path`, or stdout.
+fn open_output(path: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
+/// Writes `feed.xml` into every series folder, listing the downloaded
+/// episodes newest first with enclosures under `base_url`.
+pub fn feed(podcasts: Vec<PodcastConfig>, base_url: Option<&str>) -> io::Result<Outcome> {
+    let mut failed = 0;
+
+    for podcast in podcasts {
+        let Some(base_url) = base_url
+            .map(|url| url.trim_end_matches('/').to_string())
+            .or_else(|| podcast.base_url.clone())
+        else {
+            error!(
+                series = %podcast.name,
+                "No base_url configured; set it or pass --base-url"
+            );
+            failed += 1;
+            continue;
+        };
+        let downloader = PodcastDownloader::new(podcast.clone())?;
+        let folder = &podcast.download_folder;
+        let series_url = format!("{}/{}", base_url, feed::encode_path(&podcast.folder_name()));
+        let (channel, items) = feed::for_series(&podcast, &downloader.index(), &series_url);
+
+        let path = folder.join(FEED_FILE_NAME);
+        let tmp = folder.join(format!("{}.tmp", FEED_FILE_NAME));
+        fs::write(&tmp, feed::render(&channel, &items))?;
+        fs::rename(&tmp, &path)?;
+        println!(
+            "{}: wrote {} ({} episodes)",
+            podcast.name,
+            path.display(),
+            items.len()
+        );
+    }
+
+    Ok(if failed > 0 {
+        Outcome::Partial
+    } else {
+        Outcome::Success
+    })
+}
+
+pub async fn serve(
+    podcasts: Vec<PodcastConfig>,
+    listen: SocketAddr,
+    base_url: Option<String>,
+) -> io::Result<Outcome> {
+    server::run(podcasts, listen, base_url).await?;
+    Ok(Outcome::Success)
+}
+
+/// Syncs each series on its schedule until SIGTERM or Ctrl-C.
+pub async fn watch(
+    podcasts: Vec<PodcastConfig>,
+    limits: &Limits,
+    retry_failed: bool,
+    progress: Progress,
+) -> io::Result<Outcome> {
+    watch::run(podcasts, limits, retry_failed, progress).await?;
+    Ok(Outcome::Success)
+}
+
+/// Shows, for each series, what every selector matches on listing page
+/// `page` (or on a saved copy in `file`) and the episodes that come out of
+/// it, so selectors can be tuned without downloading anything.
+pub async fn probe(
+    podcasts: Vec<PodcastConfig>,
+    file: Option<&Path>,
+    page: usize,
+) -> io::Result<Outcome> {
+    let client = reqwest::Client::new();
+    let mut failed = false;
+
+    for podcast in podcasts {
+        let source = source::for_config(&podcast);
+        let (location, body) = match file {
+            Some(path) => (path.display().to_string(), fs::read_to_string(path)),
+            None => {
+                let url = source.page_url(page);
+                let body = fetch::get_text(&client, &url).await;
+                (url, body)
+            }
+        };
+        println!("{} ({}):", podcast.name, location);
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => {
+                error!(series = %podcast.name, "Failed to read {}: {}", location, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        if podcast.source == SourceKind::Html {
+            probe_selectors(&podcast.selectors, &body, page);
+        }
+
+        let listing = match source.parse(&body, page) {
+            Ok(listing) => listing,
+            Err(e) => {
+                error!(series = %podcast.name, "Failed to parse {}: {}", location, e);
+                failed = true;
+                continue;
+            }
+        };
+        let excluded = listing
+            .episodes
+            .iter()
+            .filter(|episode| !podcast.includes(&episode.title, &episode.url))
+            .count();
+        println!(
+            "  {} episodes, {} excluded; next page: {}",
+            listing.episodes.len(),
+            excluded,
+            if listing.has_next { "yes" } else { "no" }
+        );
+        for episode in &listing.episodes {
+            let marker = if podcast.includes(&episode.title, &episode.url) {
+                "+"
+            } else {
+                "-"
+            };
+            let date = episode
+                .broadcast_date
+                .map_or_else(|| "----------".to_string(), |date| date.to_string());
+            let duration = episode
+                .duration
+                .map(|duration| format!(" ({} min)", duration.as_secs().div_ceil(60)))
+                .unwrap_or_default();
+            println!(
+                "  [{}] {} {} {}{}",
+                marker,
+                date,
+                episode.pid.as_deref().unwrap_or("--------"),
+                episode.title,
+                duration
+            );
+            println!("      {} ({})", episode.audio_url(), episode.variant.name());
+        }
+    }
+
+    Ok(if failed {
+        Outcome::Partial
+    } else {
+        Outcome::Success
+    })
+}
+
+/// Prints the match count and the first few matches of every selector,
+/// over the whole page rather than per episode card.
+fn probe_selectors(selectors: &Selectors, html: &str, page: usize) {
+    const SAMPLES: usize = 3;
+
+    let document = Html::parse_document(html);
+    for (key, css) in selectors.css() {
+        let css = if key == "next_page" {
+            selectors.next_page(page + 1)
+        } else {
+            css.to_string()
+        };
+        let Ok(selector) = Selector::parse(&css) else {
+            println!("  {:<10} invalid selector: {}", key, css);
+            continue;
+        };
+        let matches = document.select(&selector).collect::<Vec<_>>();
+        println!("  {:<10} {:>4} matches  {}", key, matches.len(), css);
+        for element in matches.iter().take(SAMPLES) {
+            println!("      {}", describe(element, selectors));
+        }
+        if matches.len() > SAMPLES {
+            println!("      ...");
+        }
+
+        if key == "episode" {
+            for attribute in [&selectors.url_attribute, &selectors.download_attribute] {
+                let with = matches
+                    .iter()
+                    .filter(|element| element.value().attr(attribute).is_some())
+                    .count();
+                println!(
+                    "  {:<10} {:>4} of the episode links have {}",
+                    "", with, attribute
+                );
+            }
+        }
+    }
+}
+
+/// The text of a matched element, followed by the first attribute the
+/// scraper might read from it.
+fn describe(element: &ElementRef, selectors: &Selectors) -> String {
+    let value = element.value();
+    let attribute = [
+        selectors.url_attribute.as_str(),
+        "href",
+        "data-src",
+        "src",
+        "datetime",
+        "content",
+    ]
+    .into_iter()
+    .find_map(|name| value.attr(name).map(|value| format!("{}={}", name, value)));
+
+    let text = episode::text(*element).map(|text| format!("\"{}\"", text));
+    let description = [text, attribute]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let description = if description.is_empty() {
+        format!("<{}>", value.name())
+    } else {
+        description
+    };
+    match description.char_indices().nth(100) {
+        Some((end, _)) => format!("{}...", &description[..end]),
+        None => description,
+    }
+}
+
+pub fn rename(podcasts: Vec<PodcastConfig>, apply: bool) -> io::Result<Outcome> {
+    let mut pending = 0;
+
+    for podcast in podcasts {
+        let downloader = PodcastDownloader::new(podcast)?;
+        let folder = downloader.config().download_folder.clone();
+        println!("{}:", downloader.config().name);
+
+        let local = downloader.local_names()?;
+
+        // Pair every indexed episode with its file, finding files of
+        // migrated entries by the programme or media id in their name.
+        let mut index = downloader.index();
+        let mut matched = HashSet::new();
+        let mut current = Vec::new();
+        for record in index.downloaded() {
+            let file = match &record.filename {
+                Some(filename) if folder.join(filename).exists() => Some(filename.clone()),
+                _ => local
+                    .iter()
+                    .find(|name| !matched.contains(*name) && record.names_file(name))
+                    .cloned(),
+            };
+            if let Some(file) = file {
+                matched.insert(file.clone());
+                current.push((record.clone(), file));
+            }
+        }
+
+        for name in local.iter().filter(|name| !matched.contains(*name)) {
+            println!("  no index entry for {}", name);
+        }
+
+        // Files that already have the right name keep it; the rest get the
+        // first free variant of their target.
+        let sources = current
+            .iter()
+            .map(|(_, from)| from.clone())
+            .collect::<HashSet<_>>();
+        let mut targets = current
+            .iter()
+            .filter(|(record, from)| downloader.filename_for_record(record) == *from)
+            .map(|(_, from)| from.clone())
+            .collect::<HashSet<_>>();
+        let mut plan = Vec::new();
+        for (record, from) in current {
+            let wanted = downloader.filename_for_record(&record);
+            let to = if wanted == from {
+                from.clone()
+            } else {
+                naming::unique(&wanted, |name| {
+                    targets.contains(name)
+                        || (!sources.contains(name) && folder.join(name).exists())
+                })
+            };
+            targets.insert(to.clone());
+            plan.push((record, from, to));
+        }
+
+        let moves = plan
+            .iter()
+            .filter(|(_, from, to)| from != to)
+            .map(|(_, from, to)| (from.clone(), to.clone()))
+            .collect::<Vec<_>>();
+        let adoptions = plan
+            .iter()
+            .filter(|(record, from, _)| record.filename.as_ref() != Some(from))
+            .count();
+        for (record, from, to) in &plan {
+            let adopted = record.filename.as_ref() != Some(from);
+            match (adopted, from == to) {
+                (true, true) => println!("  = {} (adopted for {})", from, record.vpid),
+                (true, false) => {
+                    println!("  - {} (adopted for {})\n  + {}", from, record.vpid, to)
+                }
+                (false, false) => println!("  - {}\n  + {}", from, to),
+                (false, true) => {}
+            }
+        }
+
+        if moves.is_empty() && adoptions == 0 {
+            println!("  nothing to rename");
+            continue;
+        }
+        if !apply {
+            pending += plan
+                .iter()
+                .filter(|(record, from, to)| from != to || record.filename.as_ref() != Some(from))
+                .count();
+            continue;
+        }
+
+        // Adopted files get the size and checksum `verify` checks.
+        let mut digests = HashMap::new();
+        for (record, from, _) in &plan {
+            if record.filename.as_ref() != Some(from) {
+                digests.insert(from.clone(), index::file_digest(&folder.join(from))?);
+            }
+        }
+
+        rename_files(&folder, &moves)?;
+        index.update(plan.into_iter().map(|(record, from, to)| {
+            let (size, sha256) = match digests.remove(&from) {
+                Some((size, sha256)) => (Some(size), Some(sha256)),
+                None => (record.size, record.sha256.clone()),
+            };
+            EpisodeRecord {
+                filename: Some(to),
+                size,
+                sha256,
+                ..record
+            }
+        }));
+        if let Err(e) = index.compact() {
+            let back = moves
+                .iter()
+                .map(|(from, to)| (to.clone(), from.clone()))
+                .collect::<Vec<_>>();
+            rename_files(&folder, &back)?;
+            return Err(e);
+        }
+        println!(
+            "  renamed {} files, adopted {} files",
+            moves.len(),
+            adoptions
+        );
+    }
+
+    if pending > 0 {
+        println!(
+            "Dry run: {} files would be renamed or adopted, use --apply to do so",
+            pending
+        );
+    }
+    Ok(Outcome::Success)
+}
+
+/// Moves every `(from, to)` in `folder` through a temporary name so that
+/// swaps and chains work, undoing everything if one move fails. Target
+/// folders such as `low/` are created as needed.
+fn rename_files(folder: &Path, moves: &[(String, String)]) -> io::Result<()> {
+    for (_, to) in moves {
+        if let Some(parent) = folder.join(to).parent() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let staged = moves
+        .iter()
+        .enumerate()
+        .map(|(i, (from, to))| (from.clone(), format!(".rename-{}.tmp", i), to.clone()))
+        .collect::<Vec<_>>();
+
+    let to_tmp = staged
+        .iter()
+        .map(|(from, tmp, _)| (from.clone(), tmp.clone()))
+        .collect::<Vec<_>>();
+    move_all(folder, &to_tmp)?;
+
+    let to_target = staged
+        .iter()
+        .map(|(_, tmp, to)| (tmp.clone(), to.clone()))
+        .collect::<Vec<_>>();
+    if let Err(e) = move_all(folder, &to_target) {
+        let back = staged
+            .iter()
+            .map(|(from, tmp, _)| (tmp.clone(), from.clone()))
+            .collect::<Vec<_>>();
+        move_all(folder, &back)?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Renames `(from, to)` pairs in order, moving the finished ones back if
+/// one fails.
+fn move_all(folder: &Path, moves: &[(String, String)]) -> io::Result<()> {
+    for (done, (from, to)) in moves.iter().enumerate() {
+        if let Err(e) = fs::rename(folder.join(from), folder.join(to)) {
+            for (from, to) in moves[..done].iter().rev() {
+                fs::rename(folder.join(to), folder.join(from))?;
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rename_files_creates_folders_and_swaps() {
+        let folder = tempfile::tempdir().unwrap();
+        let folder = folder.path();
+        for (name, content) in [("a.mp3", "a"), ("b.mp3", "b"), ("c.mp3", "c")] {
+            fs::write(folder.join(name), content).unwrap();
+        }
+
+        let moves = [
+            ("a.mp3".to_string(), "low/a.mp3".to_string()),
+            ("b.mp3".to_string(), "c.mp3".to_string()),
+            ("c.mp3".to_string(), "b.mp3".to_string()),
+        ];
+        rename_files(folder, &moves).unwrap();
+
+        let read = |name: &str| fs::read_to_string(folder.join(name)).unwrap();
+        assert_eq!(read("low/a.mp3"), "a");
+        assert_eq!(read("b.mp3"), "c");
+        assert_eq!(read("c.mp3"), "b");
+        assert!(!folder.join("a.mp3").exists());
+    }
+}
@@ -0,0 +1,597 @@
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+    episode::Variant, naming, retry::RetryPolicy, schedule::Schedule, selectors::Selectors,
+    source::SourceKind, tags,
+};
+
+const SIX_MINUTE_ENGLISH: &str = "https://www.bbc.co.uk/programmes/p02pc9tn/episodes/downloads";
+const SIX_MINUTE_VOCABULARY: &str = "https://www.bbc.co.uk/programmes/p02pc9xz/episodes/downloads";
+const SIX_MINUTE_GRAMMAR: &str = "https://www.bbc.co.uk/programmes/p02pc9wq/episodes/downloads";
+
+const CONFIG_FILE_NAME: &str = "podcasts.toml";
+const DEFAULT_DOWNLOADS: usize = 8;
+const DEFAULT_MAX_PAGES: usize = 1;
+
+/// Which of the BBC audio variants to download.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Quality {
+    #[default]
+    High,
+    Low,
+    /// Both files, the low one in a `low/` subfolder.
+    Both,
+    /// The high file, or the low one when there is no high file or it
+    /// fails to download.
+    PreferHighFallbackLow,
+}
+
+impl Quality {
+    /// Whether a listed file in `variant` is worth keeping.
+    pub fn accepts(self, variant: Variant) -> bool {
+        match self {
+            Self::High => variant == Variant::High,
+            Self::Low => variant == Variant::Low,
+            Self::Both | Self::PreferHighFallbackLow => true,
+        }
+    }
+
+    /// Whether an episode counts as downloaded in any variant, rather
+    /// than only in the one listed.
+    pub fn one_per_episode(self) -> bool {
+        self == Self::PreferHighFallbackLow
+    }
+}
+
+/// How transcripts are saved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TranscriptFormat {
+    #[default]
+    Markdown,
+    Text,
+}
+
+/// A single series, with the global defaults already applied.
+#[derive(Debug, Clone)]
+pub struct PodcastConfig {
+    pub name: String,
+    pub url: String,
+    /// What `url` points at.
+    pub source: SourceKind,
+    pub download_folder: PathBuf,
+    /// Downloads of this series at a time, within the run's `Limits`.
+    pub concurrency: Option<usize>,
+    /// Which variant to take from HTML listings; feeds have only one.
+    pub quality: Quality,
+    /// Where episodes are found on HTML listings.
+    pub selectors: Selectors,
+    /// Only episodes whose title or audio URL matches are downloaded.
+    pub include: Option<Regex>,
+    /// Episodes whose title or audio URL matches are skipped.
+    pub exclude: Option<Regex>,
+    /// See `naming::render` for the placeholders.
+    pub filename_template: String,
+    /// Listing pages to walk per run; 0 follows the whole archive.
+    pub max_pages: usize,
+    /// When `watch` checks the series.
+    pub schedule: Schedule,
+    pub retry: RetryPolicy,
+    /// Write ID3v2.4 tags into downloaded files.
+    pub write_tags: bool,
+    pub artist: String,
+    /// Embed the episode or series image as cover art when tagging.
+    pub embed_artwork: bool,
+    /// Save the transcript of each downloaded episode.
+    pub transcripts: bool,
+    pub transcript_format: TranscriptFormat,
+    /// Public URL the download folders are served under, without a
+    /// trailing slash; the series folder is at `<base_url>/<folder>/`,
+    /// named like the download folder.
+    pub base_url: Option<String>,
+}
+
+impl PodcastConfig {
+    /// The series name as a URL path segment, e.g. `6-minute-english`.
+    pub fn slug(&self) -> String {
+        let mut slug = String::new();
+        for c in self.name.chars() {
+            if c.is_ascii_alphanumeric() {
+                slug.push(c.to_ascii_lowercase());
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        slug.trim_end_matches('-').to_string()
+    }
+
+    /// The name of the download folder, which `feed` expects it to be
+    /// published under; the slug when the path has none, such as `.`.
+    pub fn folder_name(&self) -> String {
+        self.download_folder
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.slug())
+    }
+
+    /// Whether `include` and `exclude` let through an episode with this
+    /// title and audio URL.
+    pub fn includes(&self, title: &str, url: &str) -> bool {
+        let matches = |regex: &Regex| regex.is_match(title) || regex.is_match(url);
+        self.include.as_ref().is_none_or(matches) && !self.exclude.as_ref().is_some_and(matches)
+    }
+}
+
+/// A `[[podcast]]` table, or the `[defaults]` every series falls back to
+/// for the settings it leaves out. `name`, `url` and `download_folder`
+/// are required in a series and not allowed in the defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeriesEntry {
+    name: Option<String>,
+    url: Option<String>,
+    download_folder: Option<PathBuf>,
+    source: Option<SourceKind>,
+    concurrency: Option<usize>,
+    quality: Option<Quality>,
+    #[serde(default)]
+    selectors: SelectorsEntry,
+    include: Option<String>,
+    exclude: Option<String>,
+    filename_template: Option<String>,
+    max_pages: Option<usize>,
+    interval: Option<String>,
+    schedule: Option<String>,
+    retry_attempts: Option<u32>,
+    retry_backoff_ms: Option<u64>,
+    retry_jitter: Option<f64>,
+    write_tags: Option<bool>,
+    artist: Option<String>,
+    embed_artwork: Option<bool>,
+    transcripts: Option<bool>,
+    transcript_format: Option<TranscriptFormat>,
+    base_url: Option<String>,
+}
+
+/// A `selectors` table; keys left out fall back to `[defaults.selectors]`
+/// and then to the built-in BBC selectors.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SelectorsEntry {
+    episode: Option<String>,
+    url_attribute: Option<String>,
+    download_attribute: Option<String>,
+    title: Option<String>,
+    synopsis: Option<String>,
+    date: Option<String>,
+    duration: Option<String>,
+    page_link: Option<String>,
+    image: Option<String>,
+    next_page: Option<String>,
+}
+
+impl SelectorsEntry {
+    fn resolve(self, defaults: &SelectorsEntry) -> Selectors {
+        let builtin = Selectors::default();
+        let pick = |value: Option<String>, default: &Option<String>, builtin: String| {
+            value.or_else(|| default.clone()).unwrap_or(builtin)
+        };
+        Selectors {
+            episode: pick(self.episode, &defaults.episode, builtin.episode),
+            url_attribute: pick(
+                self.url_attribute,
+                &defaults.url_attribute,
+                builtin.url_attribute,
+            ),
+            download_attribute: pick(
+                self.download_attribute,
+                &defaults.download_attribute,
+                builtin.download_attribute,
+            ),
+            title: pick(self.title, &defaults.title, builtin.title),
+            synopsis: pick(self.synopsis, &defaults.synopsis, builtin.synopsis),
+            date: pick(self.date, &defaults.date, builtin.date),
+            duration: pick(self.duration, &defaults.duration, builtin.duration),
+            page_link: pick(self.page_link, &defaults.page_link, builtin.page_link),
+            image: pick(self.image, &defaults.image, builtin.image),
+            next_page: pick(self.next_page, &defaults.next_page, builtin.next_page),
+        }
+    }
+}
+
+/// How many downloads a run makes at a time, over all series.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    pub downloads: usize,
+    /// Cap for every host that has none of its own in `hosts`.
+    pub per_host: Option<usize>,
+    pub hosts: HashMap<String, usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            downloads: DEFAULT_DOWNLOADS,
+            per_host: None,
+            hosts: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LimitsEntry {
+    downloads: Option<usize>,
+    per_host: Option<usize>,
+    #[serde(default)]
+    hosts: HashMap<String, usize>,
+}
+
+impl LimitsEntry {
+    fn resolve(self) -> io::Result<Limits> {
+        let limits = Limits {
+            downloads: self.downloads.unwrap_or(DEFAULT_DOWNLOADS),
+            per_host: self.per_host,
+            hosts: self
+                .hosts
+                .into_iter()
+                .map(|(host, limit)| (host.to_ascii_lowercase(), limit))
+                .collect(),
+        };
+        if limits.downloads == 0
+            || limits.per_host == Some(0)
+            || limits.hosts.values().any(|&limit| limit == 0)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "limits must be at least 1",
+            ));
+        }
+        Ok(limits)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    limits: LimitsEntry,
+    #[serde(default)]
+    defaults: SeriesEntry,
+    #[serde(default, rename = "podcast")]
+    podcasts: Vec<SeriesEntry>,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub podcasts: Vec<PodcastConfig>,
+    pub limits: Limits,
+}
+
+impl Config {
+    /// Loads `path` if given, otherwise the default location. A missing
+    /// default file falls back to the built-in BBC series; a missing
+    /// explicit file is an error.
+    pub fn load(path: Option<&Path>) -> io::Result<Self> {
+        match path {
+            Some(path) => Self::from_file(path),
+            None => match default_path() {
+                Some(path) if path.exists() => Self::from_file(&path),
+                _ => Ok(Self::builtin()),
+            },
+        }
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        fs::read_to_string(path)
+            .and_then(|content| content.parse())
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+
+    fn resolve(file: ConfigFile) -> io::Result<Self> {
+        let defaults = file.defaults;
+        let per_series = [
+            ("name", defaults.name.is_some()),
+            ("url", defaults.url.is_some()),
+            ("download_folder", defaults.download_folder.is_some()),
+        ];
+        if let Some((key, _)) = per_series.iter().find(|(_, set)| *set) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("defaults: {} can only be set on a [[podcast]]", key),
+            ));
+        }
+
+        let podcasts = file
+            .podcasts
+            .into_iter()
+            .map(|entry| {
+                let missing = |key: &str| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{}: missing {}",
+                            entry.name.as_deref().unwrap_or("[[podcast]]"),
+                            key
+                        ),
+                    )
+                };
+                let name = entry.name.clone().ok_or_else(|| missing("name"))?;
+                let url = entry.url.clone().ok_or_else(|| missing("url"))?;
+                let download_folder = entry
+                    .download_folder
+                    .clone()
+                    .ok_or_else(|| missing("download_folder"))?;
+                let concurrency = entry.concurrency.or(defaults.concurrency);
+                let retry = RetryPolicy {
+                    attempts: entry
+                        .retry_attempts
+                        .or(defaults.retry_attempts)
+                        .unwrap_or(RetryPolicy::default().attempts)
+                        .max(1),
+                    backoff: entry
+                        .retry_backoff_ms
+                        .or(defaults.retry_backoff_ms)
+                        .map(Duration::from_millis)
+                        .unwrap_or(RetryPolicy::default().backoff),
+                    jitter: entry
+                        .retry_jitter
+                        .or(defaults.retry_jitter)
+                        .unwrap_or(RetryPolicy::default().jitter),
+                };
+                if concurrency == Some(0) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: concurrency must be at least 1", name),
+                    ));
+                }
+                let filename_template = entry
+                    .filename_template
+                    .or_else(|| defaults.filename_template.clone())
+                    .unwrap_or_else(|| naming::DEFAULT_TEMPLATE.to_string());
+                naming::validate(&filename_template).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: filename_template {}", name, e),
+                    )
+                })?;
+                let selectors = entry.selectors.resolve(&defaults.selectors);
+                selectors.validate().map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: selectors.{}", name, e),
+                    )
+                })?;
+                let regex = |key: &str, pattern: Option<String>| {
+                    pattern
+                        .map(|pattern| {
+                            Regex::new(&pattern).map_err(|e| {
+                                io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    format!("{}: {}: {}", name, key, e),
+                                )
+                            })
+                        })
+                        .transpose()
+                };
+                let schedule = match (entry.interval, entry.schedule) {
+                    (None, None) => (defaults.interval.clone(), defaults.schedule.clone()),
+                    entry => entry,
+                };
+                let schedule = match schedule {
+                    (Some(_), Some(_)) => {
+                        Err("set either interval or schedule, not both".to_string())
+                    }
+                    (Some(interval), None) => Schedule::interval(&interval),
+                    (None, Some(cron)) => Schedule::cron(&cron),
+                    (None, None) => Ok(Schedule::default()),
+                }
+                .map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", name, e))
+                })?;
+                let include = regex(
+                    "include",
+                    entry.include.or_else(|| defaults.include.clone()),
+                )?;
+                let exclude = regex(
+                    "exclude",
+                    entry.exclude.or_else(|| defaults.exclude.clone()),
+                )?;
+                Ok(PodcastConfig {
+                    name,
+                    url,
+                    source: entry.source.or(defaults.source).unwrap_or_default(),
+                    download_folder,
+                    concurrency,
+                    quality: entry.quality.or(defaults.quality).unwrap_or_default(),
+                    selectors,
+                    include,
+                    exclude,
+                    filename_template,
+                    max_pages: entry
+                        .max_pages
+                        .or(defaults.max_pages)
+                        .unwrap_or(DEFAULT_MAX_PAGES),
+                    schedule,
+                    retry,
+                    write_tags: entry.write_tags.or(defaults.write_tags).unwrap_or(true),
+                    artist: entry
+                        .artist
+                        .or_else(|| defaults.artist.clone())
+                        .unwrap_or_else(|| tags::DEFAULT_ARTIST.to_string()),
+                    embed_artwork: entry
+                        .embed_artwork
+                        .or(defaults.embed_artwork)
+                        .unwrap_or(true),
+                    transcripts: entry.transcripts.or(defaults.transcripts).unwrap_or(true),
+                    transcript_format: entry
+                        .transcript_format
+                        .or(defaults.transcript_format)
+                        .unwrap_or_default(),
+                    base_url: entry
+                        .base_url
+                        .or_else(|| defaults.base_url.clone())
+                        .map(|url| url.trim_end_matches('/').to_string()),
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            podcasts,
+            limits: file.limits.resolve()?,
+        })
+    }
+
+    /// The three 6 Minute series the tool shipped with.
+    fn builtin() -> Self {
+        let series = |name: &str, url: &str, folder: &str| PodcastConfig {
+            name: name.to_string(),
+            url: url.to_string(),
+            source: SourceKind::default(),
+            download_folder: PathBuf::from(folder),
+            concurrency: None,
+            quality: Quality::default(),
+            selectors: Selectors::default(),
+            include: None,
+            exclude: None,
+            filename_template: naming::DEFAULT_TEMPLATE.to_string(),
+            max_pages: DEFAULT_MAX_PAGES,
+            schedule: Schedule::default(),
+            retry: RetryPolicy::default(),
+            write_tags: true,
+            artist: tags::DEFAULT_ARTIST.to_string(),
+            embed_artwork: true,
+            transcripts: true,
+            transcript_format: TranscriptFormat::default(),
+            base_url: None,
+        };
+
+        Self {
+            limits: Limits::default(),
+            podcasts: vec![
+                series(
+                    "6MinuteEnglish",
+                    SIX_MINUTE_ENGLISH,
+                    "./podcasts/6min_english",
+                ),
+                series(
+                    "6 Minute Vocabulary",
+                    SIX_MINUTE_VOCABULARY,
+                    "./podcasts/6min_vocabulary",
+                ),
+                series(
+                    "6 Minute Grammar",
+                    SIX_MINUTE_GRAMMAR,
+                    "./podcasts/6min_grammar",
+                ),
+            ],
+        }
+    }
+}
+
+impl FromStr for Config {
+    type Err = io::Error;
+
+    /// Reads a config file's content.
+    fn from_str(content: &str) -> io::Result<Self> {
+        let file: ConfigFile = toml::from_str(content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        Self::resolve(file)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/bbc-scraper/podcasts.toml`, or `~/.config/...` when
+/// `XDG_CONFIG_HOME` is unset.
+pub fn default_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join("bbc-scraper").join(CONFIG_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIES: &str = "[[podcast]]\nname = \"6 Minute English\"\n\
+                          url = \"https://www.bbc.co.uk/programmes/p02pc9tn/episodes/downloads\"\n\
+                          download_folder = \"podcasts/6min_english\"\n";
+
+    fn error(content: &str) -> String {
+        let e = content.parse::<Config>().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        e.to_string()
+    }
+
+    #[test]
+    fn series_values_override_defaults() {
+        let config = format!(
+            "[defaults]\nquality = \"both\"\nartist = \"BBC\"\nmax_pages = 3\n\
+             retry_attempts = 5\n{}quality = \"low\"\nmax_pages = 10\n\
+             [podcast.selectors]\ntitle = \"h2\"\n",
+            SERIES
+        )
+        .parse::<Config>()
+        .unwrap();
+        let podcast = &config.podcasts[0];
+        assert_eq!(podcast.quality, Quality::Low);
+        assert_eq!(podcast.max_pages, 10);
+        assert_eq!(podcast.artist, "BBC");
+        assert_eq!(podcast.retry.attempts, 5);
+        assert_eq!(podcast.selectors.title, "h2");
+        assert_eq!(podcast.selectors.episode, Selectors::default().episode);
+        assert_eq!(podcast.filename_template, naming::DEFAULT_TEMPLATE);
+    }
+
+    #[test]
+    fn bad_values_are_rejected() {
+        assert_eq!(
+            error(&format!("{}concurrency = 0\n", SERIES)),
+            "6 Minute English: concurrency must be at least 1"
+        );
+        assert!(
+            error(&format!("[defaults]\nexclude = \"(\"\n{}", SERIES))
+                .starts_with("6 Minute English: exclude: ")
+        );
+        assert!(
+            error(&format!("{}filename_template = \"{{day}}.mp3\"\n", SERIES))
+                .contains("unknown placeholder {day}")
+        );
+        assert!(error(&format!("{}quality = \"best\"\n", SERIES)).contains("unknown variant"));
+        assert!(error(&format!("{}colour = \"red\"\n", SERIES)).contains("unknown field"));
+    }
+
+    #[test]
+    fn example_config_loads() {
+        let config = include_str!("../podcasts.example.toml")
+            .parse::<Config>()
+            .unwrap();
+        assert!(!config.podcasts.is_empty());
+    }
+
+    #[test]
+    fn series_keys_belong_to_series() {
+        assert_eq!(
+            error(&format!(
+                "[defaults]\nurl = \"https://example.com\"\n{}",
+                SERIES
+            )),
+            "defaults: url can only be set on a [[podcast]]"
+        );
+        assert_eq!(
+            error("[[podcast]]\nname = \"Elsewhere\"\nurl = \"https://example.com\"\n"),
+            "Elsewhere: missing download_folder"
+        );
+    }
+}
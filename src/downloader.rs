@@ -0,0 +1,1074 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+use chrono::{Local, NaiveDate};
+use futures::future::join_all;
+use scraper::Html;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use tracing::{Instrument, debug, error, info, info_span, warn};
+
+use crate::{
+    artwork::{Artwork, ArtworkCache},
+    config::{PodcastConfig, Quality, TranscriptFormat},
+    episode::{self, Episode, Variant},
+    fetch,
+    index::{self, EpisodeRecord, Index},
+    limiter::Limiter,
+    mime,
+    naming::{self, NameFields},
+    progress::{DownloadProgress, Progress},
+    retry::ErrorClass,
+    source::{self, Source, SourcePage},
+    tags::{self, EpisodeTags},
+    transcript::{self, TRANSCRIPT_DIR},
+    vocabulary,
+};
+
+/// Folder, inside the download folder, for the low-quality files of a
+/// series that keeps both variants.
+pub const LOW_QUALITY_DIR: &str = "low";
+
+/// What a `download_episodes` run looks at.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SyncOptions {
+    /// Also try episodes that failed permanently on an earlier run.
+    pub retry_failed: bool,
+    /// Walk every page up to `max_pages`, past pages that are already
+    /// fully indexed, to back-fill the archive.
+    pub walk_all: bool,
+}
+
+/// What a single `download_episodes` run did.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SyncSummary {
+    /// Episode files listed on the walked pages, after `include` and
+    /// `exclude`. With `prefer-high-fallback-low` the high and low file
+    /// of an episode count once.
+    pub discovered: usize,
+    /// Discovered files or episodes that were already in the index.
+    pub skipped: usize,
+    pub downloaded: usize,
+    pub failed: usize,
+    pub downloads: Vec<DownloadReport>,
+}
+
+/// One download of a run, as it ended.
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadReport {
+    pub vpid: String,
+    pub title: String,
+    /// The variant downloaded, or tried last.
+    pub variant: Variant,
+    pub path: PathBuf,
+    /// Size of the saved file; absent when the download failed.
+    pub bytes: Option<u64>,
+    pub duration_secs: f64,
+    pub error: Option<String>,
+    pub error_class: Option<ErrorClass>,
+}
+
+/// What `verify` found in one series folder.
+#[derive(Debug, Default)]
+pub struct Verification {
+    /// Indexed files that are missing or differ from the index, each with
+    /// what is wrong.
+    pub problems: Vec<(String, String)>,
+    /// Audio files that no index entry refers to.
+    pub untracked: Vec<String>,
+    /// Entries with no file recorded, such as migrated ones that `sync`
+    /// or `rename --apply` has not adopted a file for yet.
+    pub unadopted: usize,
+}
+
+impl Verification {
+    /// Whether anything looks wrong. Unadopted entries are not: their
+    /// files are still there, only not matched yet, and are likely among
+    /// the untracked ones.
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty() && (self.untracked.is_empty() || self.unadopted > 0)
+    }
+}
+
+pub struct PodcastDownloader {
+    config: PodcastConfig,
+    index: Mutex<Index>,
+    client: reqwest::Client,
+    source: Box<dyn Source>,
+    artwork: ArtworkCache,
+    series_image_url: Mutex<Option<String>>,
+    /// The first listing page, when `listing_if_modified` fetched it.
+    first_page: Mutex<Option<String>>,
+    /// Shared with the other series of the run.
+    limiter: Arc<Limiter>,
+    /// This series' own cap, from `concurrency`.
+    slots: Option<Semaphore>,
+    progress: Progress,
+}
+
+impl PodcastDownloader {
+    /// A downloader with the default `Limits` to itself.
+    pub fn new(config: PodcastConfig) -> io::Result<Self> {
+        Self::with_limiter(config, Arc::new(Limiter::default()))
+    }
+
+    /// A downloader whose downloads take slots from `limiter`.
+    pub fn with_limiter(config: PodcastConfig, limiter: Arc<Limiter>) -> io::Result<Self> {
+        fs::create_dir_all(&config.download_folder)?;
+
+        let index = Index::open(&config.download_folder, &config.name)?;
+        Ok(Self {
+            artwork: ArtworkCache::new(&config.download_folder),
+            source: source::for_config(&config),
+            limiter,
+            slots: config.concurrency.map(Semaphore::new),
+            progress: Progress::default(),
+            config,
+            index: Mutex::new(index),
+            client: reqwest::Client::new(),
+            series_image_url: Mutex::new(None),
+            first_page: Mutex::new(None),
+        })
+    }
+
+    /// Shows the bars of downloads on `progress`.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn config(&self) -> &PodcastConfig {
+        &self.config
+    }
+
+    pub fn index(&self) -> MutexGuard<'_, Index> {
+        self.index.lock().unwrap()
+    }
+
+    /// Every episode on the listing pages in the configured quality that
+    /// `include` and `exclude` let through, whether or not it is already
+    /// downloaded.
+    pub async fn fetch_episodes(&self) -> io::Result<Vec<Episode>> {
+        self.collect_episodes(false).await
+    }
+
+    /// Walks the listing pages up to `max_pages`. With `stop_when_known`
+    /// set, stops after the first page whose episodes are all in the index.
+    async fn collect_episodes(&self, stop_when_known: bool) -> io::Result<Vec<Episode>> {
+        let mut episodes = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let fetched = match page {
+                1 => self.first_page.lock().unwrap().take(),
+                _ => None,
+            };
+            let SourcePage {
+                episodes: mut page_episodes,
+                has_next,
+                series_image_url,
+            } = match fetched {
+                Some(body) => self.source.parse(&body, page)?,
+                None => {
+                    self.config
+                        .retry
+                        .run(&format!("Fetching {}", self.source.page_url(page)), || {
+                            self.source.fetch_page(&self.client, page)
+                        })
+                        .await?
+                }
+            };
+            if let Some(url) = series_image_url {
+                *self.series_image_url.lock().unwrap() = Some(url);
+            }
+            debug!(
+                "Page {}: {} episodes, next page: {}",
+                page,
+                page_episodes.len(),
+                has_next
+            );
+            let empty = page_episodes.is_empty();
+            if empty && page == 1 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "no episodes on {}: {}",
+                        self.source.page_url(page),
+                        self.source.nothing_found()
+                    ),
+                ));
+            }
+            page_episodes.retain(|episode| self.config.includes(&episode.title, &episode.url));
+            let all_known = page_episodes.iter().all(|episode| self.is_known(episode));
+            episodes.extend(page_episodes);
+
+            if empty || !has_next || (stop_when_known && all_known) {
+                break;
+            }
+            if self.config.max_pages != 0 && page >= self.config.max_pages {
+                break;
+            }
+            page += 1;
+        }
+
+        Ok(episodes)
+    }
+
+    /// Fetches the first listing page unless the server says it has not
+    /// changed since the response `validators` came from. A fetched page
+    /// is kept for the next walk of the listing, and its validators are
+    /// returned.
+    pub async fn listing_if_modified(
+        &self,
+        validators: &fetch::Validators,
+    ) -> io::Result<Option<fetch::Validators>> {
+        let url = self.source.page_url(1);
+        let fetched = self
+            .config
+            .retry
+            .run(&format!("Fetching {}", url), || {
+                fetch::get_text_if_modified(&self.client, &url, validators)
+            })
+            .await?;
+        Ok(fetched.map(|(body, validators)| {
+            *self.first_page.lock().unwrap() = Some(body);
+            validators
+        }))
+    }
+
+    async fn fetch_html(&self, url: &str) -> io::Result<String> {
+        fetch::get_text(&self.client, url).await
+    }
+
+    /// Downloads the episodes that are not in the index yet. Episodes that
+    /// failed permanently before are only tried again with `retry_failed`.
+    #[tracing::instrument(name = "sync", skip_all, fields(series = %self.config.name))]
+    pub async fn download_episodes(&self, options: SyncOptions) -> io::Result<SyncSummary> {
+        info!("Checking for new {} episodes...", self.config.name);
+        let retry_failed = options.retry_failed;
+
+        // Collect all download links first
+        let episodes = self
+            .collect_episodes(!(retry_failed || options.walk_all))
+            .await?;
+        for episode in &episodes {
+            self.backfill(episode)?;
+        }
+        // Count what would be downloaded: with `prefer-high-fallback-low`
+        // the two files of an episode are one download.
+        let candidates = self.pair_fallbacks(episodes);
+        let discovered = candidates.len();
+        let download_tasks = candidates
+            .into_iter()
+            .filter(|(episode, _)| {
+                if retry_failed {
+                    !self.is_already_downloaded(episode)
+                } else {
+                    !self.is_known(episode)
+                }
+            })
+            .collect::<Vec<_>>();
+        let skipped = discovered - download_tasks.len();
+
+        // Give every episode its final name up front so that two episodes
+        // rendering to the same name cannot race for it.
+        let mut claimed = self
+            .index()
+            .downloaded()
+            .filter_map(|record| record.filename.clone())
+            .collect::<HashSet<_>>();
+        let download_tasks = download_tasks
+            .into_iter()
+            .map(|(episode, fallback)| {
+                let filename = naming::unique(&self.filename_for(&episode), |name| {
+                    claimed.contains(name) || self.config.download_folder.join(name).exists()
+                });
+                claimed.insert(filename.clone());
+                (episode, fallback, filename)
+            })
+            .collect::<Vec<_>>();
+
+        let total = download_tasks.len();
+        if total == 0 {
+            info!("No new {} episodes found", self.config.name);
+            return Ok(SyncSummary {
+                discovered,
+                skipped,
+                ..SyncSummary::default()
+            });
+        }
+
+        info!(
+            "Found {} new {} episodes, downloading...",
+            total, self.config.name
+        );
+
+        // Use futures::future::join_all for concurrent downloads without
+        // spawning; the limiter decides how many run at once.
+        let overall = self.progress.series(&self.config.name, total);
+        let completed = Arc::new(AtomicUsize::new(0));
+        let failed = Arc::new(AtomicUsize::new(0));
+
+        let download_futures = download_tasks
+            .into_iter()
+            .map(|(episode, fallback, filename)| {
+                let completed = Arc::clone(&completed);
+                let failed = Arc::clone(&failed);
+                let overall = &overall;
+                let span = info_span!("episode", vpid = %episode.vpid, file = %filename);
+
+                async move {
+                    let _series = match &self.slots {
+                        Some(slots) => Some(slots.acquire().await.expect("never closed")),
+                        None => None,
+                    };
+                    let _permit = self.limiter.acquire(&episode.audio_url()).await;
+                    let bar = overall.download(&filename);
+                    let started = Instant::now();
+
+                    let mut tried = &episode;
+                    let mut result = self.download(tried, &filename, &bar).await;
+                    if result.is_err()
+                        && let Some(low) = &fallback
+                    {
+                        warn!("Trying the low-quality file of {}", filename);
+                        tried = low;
+                        result = self.download(tried, &filename, &bar).await;
+                    }
+                    drop(bar);
+                    overall.inc();
+
+                    let mut report = DownloadReport {
+                        vpid: tried.vpid.clone(),
+                        title: tried.title.clone(),
+                        variant: tried.variant,
+                        path: self.config.download_folder.join(&filename),
+                        bytes: None,
+                        duration_secs: started.elapsed().as_secs_f64(),
+                        error: None,
+                        error_class: None,
+                    };
+                    match result {
+                        Ok(size) => {
+                            let comp_count = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                            info!("Downloaded {}/{}: {}", comp_count, total, filename);
+                            report.bytes = Some(size);
+                        }
+                        Err(e) => {
+                            failed.fetch_add(1, Ordering::SeqCst);
+                            report.error_class = Some(ErrorClass::of(&e));
+                            report.error = Some(e.to_string());
+                        }
+                    }
+                    report
+                }
+                .instrument(span)
+            })
+            .collect::<Vec<_>>();
+
+        // Wait for all downloads to complete
+        let downloads = join_all(download_futures).await;
+
+        let completed = completed.load(Ordering::SeqCst);
+        let failed = failed.load(Ordering::SeqCst);
+
+        overall.finish(&format!("{} completed, {} failed", completed, failed));
+        info!(
+            "{}: {} completed, {} failed",
+            self.config.name, completed, failed
+        );
+
+        Ok(SyncSummary {
+            discovered,
+            skipped,
+            downloaded: completed,
+            failed,
+            downloads,
+        })
+    }
+
+    /// Downloads `episode` to `filename`, tags it, saves its transcript
+    /// and records it in the index, returning the file's size. Permanent
+    /// failures are recorded too.
+    async fn download(
+        &self,
+        episode: &Episode,
+        filename: &str,
+        progress: &DownloadProgress,
+    ) -> io::Result<u64> {
+        let filepath = self.config.download_folder.join(filename);
+        if let Some(parent) = filepath.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut record = self.new_record(episode, filename);
+        let source = episode.audio_url();
+        debug!("Downloading {} to {}", source, filepath.display());
+        let result = self
+            .config
+            .retry
+            .run(&format!("Downloading {}", filename), || {
+                fetch::download_file(&self.client, &source, &filepath, |bytes, total| {
+                    progress.update(bytes, total)
+                })
+            })
+            .await;
+
+        match result {
+            Ok((size, sha256)) => {
+                record.size = Some(size);
+                record.sha256 = Some(sha256);
+                if self.config.write_tags
+                    && let Err(e) = self.tag_episode(&mut record).await
+                {
+                    warn!("Failed to tag {}: {}", filename, e);
+                }
+                if self.config.transcripts {
+                    // The other variant may have fetched it already.
+                    let saved = self
+                        .index()
+                        .variants(&record.vpid)
+                        .find(|other| other.transcript.is_some())
+                        .cloned();
+                    match saved {
+                        Some(other) => record.copy_transcript(&other),
+                        None => match self.save_transcript(&mut record).await {
+                            Ok(true) => {}
+                            Ok(false) => info!("No transcript found for {}", filename),
+                            Err(e) => {
+                                warn!("Failed to save transcript of {}: {}", filename, e)
+                            }
+                        },
+                    }
+                }
+                if let Err(e) = self.index().insert(record) {
+                    error!("Failed to record download: {}", e);
+                }
+                Ok(size)
+            }
+            Err(e) => {
+                let class = ErrorClass::of(&e);
+                error!(?class, "Failed to download {}: {}", filename, e);
+                if class == ErrorClass::Permanent {
+                    record.failure = Some(e.to_string());
+                    if let Err(e) = self.index().insert(record) {
+                        error!("Failed to record failure: {}", e);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// With `prefer-high-fallback-low`, turns the listed variants of an
+    /// episode into one download of the high file with the low one as its
+    /// fallback. Otherwise every listed file is its own download.
+    fn pair_fallbacks(&self, episodes: Vec<Episode>) -> Vec<(Episode, Option<Episode>)> {
+        if !self.config.quality.one_per_episode() {
+            return episodes
+                .into_iter()
+                .map(|episode| (episode, None))
+                .collect();
+        }
+
+        let high = episodes
+            .iter()
+            .filter(|episode| episode.variant == Variant::High)
+            .map(|episode| episode.vpid.clone())
+            .collect::<HashSet<_>>();
+        let mut low = episodes
+            .iter()
+            .filter(|episode| episode.variant == Variant::Low)
+            .map(|episode| (episode.vpid.clone(), episode.clone()))
+            .collect::<HashMap<_, _>>();
+        episodes
+            .into_iter()
+            .filter_map(|episode| match episode.variant {
+                Variant::High => {
+                    let fallback = low.remove(&episode.vpid);
+                    Some((episode, fallback))
+                }
+                Variant::Low if high.contains(&episode.vpid) => None,
+                Variant::Low => Some((episode, None)),
+            })
+            .collect()
+    }
+
+    /// Whether the index has the episode downloaded: in the listed
+    /// variant, or in either when the series keeps one file per episode.
+    pub fn is_already_downloaded(&self, episode: &Episode) -> bool {
+        self.has_record(episode, EpisodeRecord::is_downloaded)
+    }
+
+    /// Like `is_already_downloaded`, but failures count too.
+    pub fn is_known(&self, episode: &Episode) -> bool {
+        self.has_record(episode, |_| true)
+    }
+
+    fn has_record(&self, episode: &Episode, matches: impl Fn(&EpisodeRecord) -> bool) -> bool {
+        let index = self.index();
+        if self.config.quality.one_per_episode() {
+            index.variants(&episode.vpid).any(matches)
+        } else {
+            index
+                .get(&episode.vpid, episode.variant)
+                .is_some_and(matches)
+        }
+    }
+
+    /// The audio files of the series relative to the download folder,
+    /// with `/` separators as the index records them, sorted.
+    pub fn local_names(&self) -> io::Result<Vec<String>> {
+        let folder = &self.config.download_folder;
+        Ok(self
+            .local_files()?
+            .iter()
+            .filter_map(|path| {
+                let name = path.strip_prefix(folder).ok()?.to_str()?;
+                Some(name.replace('\\', "/"))
+            })
+            .collect())
+    }
+
+    /// Checks every indexed file against its recorded size and checksum,
+    /// and looks for audio files the index does not know.
+    pub fn verify(&self) -> io::Result<Verification> {
+        let folder = &self.config.download_folder;
+        let index = self.index();
+        let mut verification = Verification::default();
+        let mut tracked = HashSet::new();
+        for record in index.downloaded() {
+            let Some(filename) = &record.filename else {
+                verification.unadopted += 1;
+                continue;
+            };
+            tracked.insert(filename.clone());
+
+            let problem = match fs::read(folder.join(filename)) {
+                Err(e) => Some(e.to_string()),
+                Ok(bytes) if record.size.is_some_and(|size| size != bytes.len() as u64) => {
+                    Some("size differs from index".to_string())
+                }
+                Ok(bytes)
+                    if record
+                        .sha256
+                        .as_ref()
+                        .is_some_and(|sha256| *sha256 != index::sha256_hex(&bytes)) =>
+                {
+                    Some("checksum differs from index".to_string())
+                }
+                Ok(_) => None,
+            };
+            if let Some(problem) = problem {
+                verification.problems.push((filename.clone(), problem));
+            }
+        }
+        drop(index);
+
+        verification.untracked = self
+            .local_names()?
+            .into_iter()
+            .filter(|name| !tracked.contains(name))
+            .collect();
+        Ok(verification)
+    }
+
+    /// Paths of the audio files in the download folder and its
+    /// `LOW_QUALITY_DIR`, sorted.
+    pub fn local_files(&self) -> io::Result<Vec<PathBuf>> {
+        let folder = &self.config.download_folder;
+        let mut files = Vec::new();
+        for dir in [folder.clone(), folder.join(LOW_QUALITY_DIR)] {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound && dir != *folder => continue,
+                Err(e) => return Err(e),
+            };
+            files.extend(
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| mime::is_audio(&path.to_string_lossy())),
+            );
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// The local filename for a listed episode, from the configured
+    /// template. Collisions are not resolved here.
+    pub fn filename_for(&self, episode: &Episode) -> String {
+        let name = self.render_name(
+            Some(&episode.title),
+            episode.pid.as_deref(),
+            &episode.vpid,
+            episode.broadcast_date,
+            &episode.url,
+        );
+        self.variant_path(name, episode.variant)
+    }
+
+    /// The filename the configured template gives an indexed episode.
+    pub fn filename_for_record(&self, record: &EpisodeRecord) -> String {
+        let name = self.render_name(
+            record.title.as_deref(),
+            record.pid.as_deref(),
+            &record.vpid,
+            record.published,
+            &record.url,
+        );
+        self.variant_path(name, record.variant())
+    }
+
+    /// `name` inside `LOW_QUALITY_DIR` for the low file of a series that
+    /// keeps both variants.
+    fn variant_path(&self, name: String, variant: Variant) -> String {
+        if self.config.quality == Quality::Both && variant == Variant::Low {
+            format!("{}/{}", LOW_QUALITY_DIR, name)
+        } else {
+            name
+        }
+    }
+
+    fn render_name(
+        &self,
+        title: Option<&str>,
+        pid: Option<&str>,
+        vpid: &str,
+        date: Option<NaiveDate>,
+        url: &str,
+    ) -> String {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        let ext = path
+            .rsplit_once('.')
+            .map(|(_, ext)| ext)
+            .filter(|ext| !ext.contains('/'))
+            .unwrap_or("mp3");
+        naming::render(
+            &self.config.filename_template,
+            &NameFields {
+                series: &self.config.name,
+                date,
+                title: title.unwrap_or(vpid),
+                pid,
+                vpid,
+                ext,
+            },
+        )
+    }
+
+    /// Writes the ID3 tags of an episode into its file and refreshes the
+    /// record's size and checksum to match. Returns whether the file was
+    /// tagged: files other than MP3s are left alone.
+    pub async fn tag_episode(&self, record: &mut EpisodeRecord) -> io::Result<bool> {
+        let Some(filename) = &record.filename else {
+            return Ok(false);
+        };
+        let path = self.config.download_folder.join(filename);
+        if !tags::supports(&path) {
+            debug!(
+                "Not tagging {}: ID3 tags are only written into MP3s",
+                filename
+            );
+            return Ok(false);
+        }
+        let track = self.index().track_number(record);
+        let title = record.title.clone().unwrap_or_else(|| record.vpid.clone());
+        let album = self.config.name.clone();
+        let artist = self.config.artist.clone();
+        let date = record.date();
+        let comment = record.synopsis.clone();
+        let cover = if self.config.embed_artwork {
+            self.cover_for(record).await
+        } else {
+            None
+        };
+
+        let (size, sha256) = tokio::task::spawn_blocking(move || {
+            tags::write(
+                &path,
+                &EpisodeTags {
+                    title: &title,
+                    album: &album,
+                    artist: &artist,
+                    date,
+                    track,
+                    comment: comment.as_deref(),
+                    cover: cover.as_ref(),
+                },
+            )?;
+            index::file_digest(&path)
+        })
+        .await
+        .map_err(io::Error::other)??;
+
+        record.size = Some(size);
+        record.sha256 = Some(sha256);
+        Ok(true)
+    }
+
+    /// Finds the transcript of an episode on its Learning English page and
+    /// saves it, and its PDF when there is one, under `transcripts/`.
+    /// Returns `false` when the episode has no transcript page.
+    pub async fn save_transcript(&self, record: &mut EpisodeRecord) -> io::Result<bool> {
+        let Some(page_url) = record
+            .page_url
+            .clone()
+            .or_else(|| record.pid.as_deref().map(episode::programme_url))
+        else {
+            return Ok(false);
+        };
+        let retry = &self.config.retry;
+
+        let html = retry
+            .run(&format!("Fetching {}", page_url), || {
+                self.fetch_html(&page_url)
+            })
+            .await?;
+        let Some(url) = transcript::learning_english_url(&page_url, &Html::parse_document(&html))
+        else {
+            return Ok(false);
+        };
+        let html = if url == page_url {
+            html
+        } else {
+            retry
+                .run(&format!("Fetching {}", url), || self.fetch_html(&url))
+                .await?
+        };
+        let format = self.config.transcript_format;
+        let Some(found) = transcript::parse(&Html::parse_document(&html), format) else {
+            return Ok(false);
+        };
+
+        let dir = self.config.download_folder.join(TRANSCRIPT_DIR);
+        tokio::fs::create_dir_all(&dir).await?;
+        let stem = record.pid.clone().unwrap_or_else(|| record.vpid.clone());
+        let title = record.title.as_deref().unwrap_or(&record.vpid);
+        let content = match format {
+            TranscriptFormat::Markdown => format!("# {}\n\n{}", title, found.content),
+            TranscriptFormat::Text => format!("{}\n\n{}", title, found.content),
+        };
+        let name = format!("{}.{}", stem, format.extension());
+        tokio::fs::write(dir.join(&name), content).await?;
+        let path = format!("{}/{}", TRANSCRIPT_DIR, name);
+        if let Some(old) = record.transcript.replace(path.clone())
+            && old != path
+        {
+            // Saved before in the other format.
+            let _ = tokio::fs::remove_file(self.config.download_folder.join(old)).await;
+        }
+        record.transcript_url = Some(url);
+        record.vocabulary = vocabulary::parse(&found.content, &stem);
+
+        if let Some(pdf_url) = found.pdf_url {
+            let bytes = retry
+                .run(&format!("Downloading {}", pdf_url), || async {
+                    self.client
+                        .get(&pdf_url)
+                        .send()
+                        .await
+                        .and_then(reqwest::Response::error_for_status)
+                        .map_err(io::Error::other)?
+                        .bytes()
+                        .await
+                        .map_err(io::Error::other)
+                })
+                .await?;
+            let name = format!("{}.pdf", stem);
+            tokio::fs::write(dir.join(&name), bytes).await?;
+            record.transcript_pdf = Some(format!("{}/{}", TRANSCRIPT_DIR, name));
+        }
+
+        Ok(true)
+    }
+
+    /// The episode image, falling back to the series image; `None` keeps
+    /// whatever cover the file already has.
+    async fn cover_for(&self, record: &EpisodeRecord) -> Option<Artwork> {
+        if let Some(url) = &record.image_url {
+            match self.artwork.episode(&self.client, url).await {
+                Ok(artwork) => return Some(artwork),
+                Err(e) => warn!("Failed to fetch artwork {}: {}", url, e),
+            }
+        }
+
+        let series_url = self.series_image_url.lock().unwrap().clone();
+        self.artwork
+            .series(&self.client, series_url.as_deref())
+            .await
+            .ok()
+    }
+
+    /// Fills in what the listing knows about an indexed episode that is
+    /// missing from its record, such as one migrated from the old index,
+    /// and adopts its file once the programme id is known.
+    fn backfill(&self, episode: &Episode) -> io::Result<()> {
+        let Some(mut record) = self.index().get(&episode.vpid, episode.variant).cloned() else {
+            return Ok(());
+        };
+        let filled = episode.fill(&mut record);
+        let adopted = self.adopt(&mut record)?;
+        if filled || adopted {
+            self.index().insert(record)?;
+        }
+        Ok(())
+    }
+
+    /// Records the file of an indexed episode that has none, when an
+    /// audio file that no other entry claims has its programme or media id
+    /// in its name. A file in the folder the template would put it in is
+    /// preferred.
+    pub fn adopt(&self, record: &mut EpisodeRecord) -> io::Result<bool> {
+        if record.filename.is_some() || !record.is_downloaded() {
+            return Ok(false);
+        }
+        let claimed = self
+            .index()
+            .episodes()
+            .filter_map(|other| other.filename.clone())
+            .collect::<HashSet<_>>();
+        let candidates = self
+            .local_names()?
+            .into_iter()
+            .filter(|name| !claimed.contains(name) && record.names_file(name))
+            .collect::<Vec<_>>();
+        let wanted = self.filename_for_record(record);
+        let Some(name) = candidates
+            .iter()
+            .find(|name| parent(name) == parent(&wanted))
+            .or(candidates.first())
+        else {
+            return Ok(false);
+        };
+
+        let (size, sha256) = index::file_digest(&self.config.download_folder.join(name))?;
+        debug!("Adopted {} for {}", name, record.vpid);
+        record.filename = Some(name.clone());
+        record.size = Some(size);
+        record.sha256 = Some(sha256);
+        Ok(true)
+    }
+
+    fn new_record(&self, episode: &Episode, filename: &str) -> EpisodeRecord {
+        EpisodeRecord {
+            vpid: episode.vpid.clone(),
+            pid: episode.pid.clone(),
+            title: Some(episode.title.clone()),
+            synopsis: episode.synopsis.clone(),
+            duration: episode.duration.map(|duration| duration.as_secs()),
+            page_url: episode.page_url.clone(),
+            image_url: episode.image_url.clone(),
+            series: self.config.name.clone(),
+            url: episode.url.clone(),
+            variant: Some(episode.variant),
+            filename: Some(filename.to_string()),
+            size: None,
+            sha256: None,
+            published: episode.broadcast_date,
+            downloaded_at: Local::now(),
+            transcript: None,
+            transcript_pdf: None,
+            transcript_url: None,
+            vocabulary: Vec::new(),
+            failure: None,
+        }
+    }
+}
+
+/// The folder part of a `/`-separated relative name.
+fn parent(name: &str) -> &str {
+    name.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::config::Config;
+
+    const LEGACY: &str = "20240103071500 https://open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download/proto/https/vpid/p0lp7521.mp3\n";
+    /// How the old scraper named the file of that entry.
+    const LEGACY_FILE: &str = "_A_vaccine_for_cancer_-_p0ktxjlj.mp3";
+
+    fn downloader(folder: &Path) -> PodcastDownloader {
+        let config = format!(
+            "[[podcast]]\nname = \"6 Minute English\"\nurl = \"https://www.bbc.co.uk/programmes/p02pc9tn/episodes/downloads\"\ndownload_folder = {:?}\n",
+            folder
+        )
+        .parse::<Config>()
+        .unwrap();
+        PodcastDownloader::new(config.podcasts.into_iter().next().unwrap()).unwrap()
+    }
+
+    fn listed() -> Episode {
+        Episode {
+            vpid: "p0lp7521".to_string(),
+            pid: Some("p0ktxjlj".to_string()),
+            title: "A vaccine for cancer".to_string(),
+            synopsis: None,
+            broadcast_date: NaiveDate::from_ymd_opt(2024, 1, 2),
+            duration: None,
+            page_url: None,
+            image_url: None,
+            url: "//open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download/proto/https/vpid/p0lp7521.mp3".to_string(),
+            variant: Variant::High,
+        }
+    }
+
+    /// A listing of two episodes, each with a high and a low file, whose
+    /// audio links point back at `base`.
+    fn paired_listing(base: &str) -> String {
+        ["p0aaaaaa", "p0bbbbbb"]
+            .iter()
+            .map(|vpid| {
+                format!(
+                    "<div><a href=\"{base}/audio-nondrm-download/vpid/{vpid}.mp3\">High</a>\
+                     <a href=\"{base}/audio-nondrm-download-low/vpid/{vpid}.mp3\">Low</a></div>"
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn paired_files_count_once() {
+        use axum::{Router, extract::State, http::header, response::IntoResponse, routing::get};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route(
+                "/listing",
+                get(|State(base): State<String>| async move {
+                    axum::response::Html(paired_listing(&base))
+                }),
+            )
+            .route(
+                "/{set}/vpid/{file}",
+                get(|| async {
+                    ([(header::CONTENT_TYPE, "audio/mpeg")], "ID3 audio").into_response()
+                }),
+            )
+            .with_state(base.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let folder = tempfile::tempdir().unwrap();
+        let config = format!(
+            "[[podcast]]\nname = \"Paired\"\nurl = \"{}/listing\"\ndownload_folder = {:?}\n\
+             quality = \"prefer-high-fallback-low\"\ntranscripts = false\n\
+             write_tags = false\nembed_artwork = false\n",
+            base,
+            folder.path()
+        )
+        .parse::<Config>()
+        .unwrap();
+        let downloader =
+            PodcastDownloader::new(config.podcasts.into_iter().next().unwrap()).unwrap();
+        downloader
+            .index()
+            .insert(
+                serde_json::from_str(&format!(
+                    r#"{{"vpid":"p0aaaaaa","series":"Paired","url":"{}/audio-nondrm-download-low/vpid/p0aaaaaa.mp3","variant":"low","filename":"a.mp3","downloaded_at":"2025-07-18T08:00:00+01:00"}}"#,
+                    base
+                ))
+                .unwrap(),
+            )
+            .unwrap();
+
+        let summary = downloader
+            .download_episodes(SyncOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            (
+                summary.discovered,
+                summary.skipped,
+                summary.downloaded,
+                summary.failed
+            ),
+            (2, 1, 1, 0)
+        );
+        assert_eq!(summary.downloads[0].vpid, "p0bbbbbb");
+        assert_eq!(summary.downloads[0].variant, Variant::High);
+    }
+
+    #[test]
+    fn backfill_adopts_migrated_file() {
+        let folder = tempfile::tempdir().unwrap();
+        fs::write(folder.path().join(".podcast_index"), LEGACY).unwrap();
+        fs::write(folder.path().join(LEGACY_FILE), b"ID3 audio").unwrap();
+
+        let downloader = downloader(folder.path());
+        assert_eq!(downloader.verify().unwrap().unadopted, 1);
+
+        downloader.backfill(&listed()).unwrap();
+        let record = downloader
+            .index()
+            .get("p0lp7521", Variant::High)
+            .cloned()
+            .unwrap();
+        assert_eq!(record.pid.as_deref(), Some("p0ktxjlj"));
+        assert_eq!(record.filename.as_deref(), Some(LEGACY_FILE));
+        assert_eq!(record.size, Some(9));
+        assert_eq!(
+            record.sha256.as_deref(),
+            Some(index::sha256_hex(b"ID3 audio").as_str())
+        );
+
+        let verification = downloader.verify().unwrap();
+        assert!(verification.is_clean(), "{:?}", verification);
+
+        // The adoption is saved, not only held in memory.
+        drop(downloader);
+        assert!(self::downloader(folder.path()).verify().unwrap().is_clean());
+    }
+
+    #[test]
+    fn verify_freshly_migrated_index() {
+        let folder = tempfile::tempdir().unwrap();
+        fs::write(folder.path().join(".podcast_index"), LEGACY).unwrap();
+        fs::write(folder.path().join(LEGACY_FILE), b"ID3 audio").unwrap();
+
+        let verification = downloader(folder.path()).verify().unwrap();
+        assert!(verification.problems.is_empty());
+        assert_eq!(verification.unadopted, 1);
+        assert_eq!(verification.untracked, [LEGACY_FILE]);
+        assert!(verification.is_clean());
+    }
+
+    #[test]
+    fn verify_reports_untracked_files_once_all_are_adopted() {
+        let folder = tempfile::tempdir().unwrap();
+        fs::write(folder.path().join(".podcast_index"), LEGACY).unwrap();
+        fs::write(folder.path().join(LEGACY_FILE), b"ID3 audio").unwrap();
+        fs::write(folder.path().join("stray.mp3"), b"ID3").unwrap();
+
+        let downloader = downloader(folder.path());
+        downloader.backfill(&listed()).unwrap();
+        let verification = downloader.verify().unwrap();
+        assert_eq!(verification.unadopted, 0);
+        assert_eq!(verification.untracked, ["stray.mp3"]);
+        assert!(!verification.is_clean());
+    }
+
+    #[test]
+    fn backfill_leaves_files_of_other_entries() {
+        let folder = tempfile::tempdir().unwrap();
+        fs::write(folder.path().join(".podcast_index"), LEGACY).unwrap();
+        fs::write(folder.path().join("_Another_one_-_p0kzzzzz.mp3"), b"ID3").unwrap();
+
+        let downloader = downloader(folder.path());
+        downloader.backfill(&listed()).unwrap();
+        let verification = downloader.verify().unwrap();
+        assert_eq!(verification.unadopted, 1);
+        assert_eq!(verification.untracked, ["_Another_one_-_p0kzzzzz.mp3"]);
+    }
+}
@@ -0,0 +1,429 @@
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::{artwork, index::EpisodeRecord, selectors::Selectors};
+
+const PROGRAMMES_URL: &str = "https://www.bbc.co.uk/programmes/";
+/// Media set of the low-bitrate BBC downloads.
+const LOW_QUALITY_MEDIASET: &str = "audio-nondrm-download-low";
+
+/// One of the encodings an episode is offered in. Listings link a high-
+/// and a low-bitrate file under the same vpid; feeds have a single file,
+/// counted as high.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Variant {
+    #[default]
+    High,
+    Low,
+}
+
+impl Variant {
+    /// The variant of a BBC download URL.
+    pub fn of_url(url: &str) -> Self {
+        if url.contains(LOW_QUALITY_MEDIASET) {
+            Self::Low
+        } else {
+            Self::High
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Low => "low",
+        }
+    }
+}
+
+/// An episode as listed on a series page.
+#[derive(Debug, Clone)]
+pub struct Episode {
+    /// Media id of the audio file.
+    pub vpid: String,
+    /// Programme id of the episode page.
+    pub pid: Option<String>,
+    pub title: String,
+    pub synopsis: Option<String>,
+    pub broadcast_date: Option<NaiveDate>,
+    pub duration: Option<Duration>,
+    pub page_url: Option<String>,
+    pub image_url: Option<String>,
+    /// The audio link as it appears on the page, usually protocol-relative.
+    pub url: String,
+    pub variant: Variant,
+}
+
+impl Episode {
+    /// The episode behind the audio link `link`, with whatever its
+    /// episode card says about it. `None` if the link has no usable file
+    /// name.
+    pub fn from_link(link: ElementRef, selectors: &Selectors) -> Option<Self> {
+        let url = link.value().attr(&selectors.url_attribute)?;
+        let vpid = crate::index::vpid_from_url(url)?;
+        let (download_title, download_pid) = link
+            .value()
+            .attr(&selectors.download_attribute)
+            .map(parse_download_name)
+            .unwrap_or_default();
+        let card = card(link, selectors);
+
+        let page_url = card.and_then(|card| page_url(card, selectors));
+        let pid = download_pid
+            .or_else(|| {
+                card.and_then(|card| card.value().attr("data-pid"))
+                    .filter(|pid| is_pid(pid))
+                    .map(str::to_string)
+            })
+            .or_else(|| page_url.as_deref().and_then(pid_from_page_url));
+        let page_url = page_url.or_else(|| pid.as_deref().map(programme_url));
+
+        Some(Self {
+            vpid: vpid.to_string(),
+            title: card
+                .and_then(|card| text_of(card, &selectors.title))
+                .or(download_title)
+                .unwrap_or_else(|| vpid.to_string()),
+            pid,
+            synopsis: card.and_then(|card| text_of(card, &selectors.synopsis)),
+            broadcast_date: card.and_then(|card| broadcast_date(card, &selectors.date)),
+            duration: card.and_then(|card| duration(card, &selectors.duration)),
+            page_url,
+            image_url: card.and_then(|card| artwork::card_image_url(card, &selectors.image)),
+            url: url.to_string(),
+            variant: Variant::of_url(url),
+        })
+    }
+
+    /// The audio URL to request.
+    pub fn audio_url(&self) -> String {
+        absolute(&self.url)
+    }
+
+    /// Copies what the listing knows into `record` where the record has
+    /// nothing yet, returning whether anything changed.
+    pub fn fill(&self, record: &mut EpisodeRecord) -> bool {
+        fn fill<T: Clone>(field: &mut Option<T>, value: &Option<T>) -> bool {
+            if field.is_none() && value.is_some() {
+                *field = value.clone();
+                true
+            } else {
+                false
+            }
+        }
+
+        let duration = self.duration.map(|d| d.as_secs());
+        // Non-short-circuiting so every field gets filled.
+        fill(&mut record.pid, &self.pid)
+            | fill(&mut record.title, &Some(self.title.clone()))
+            | fill(&mut record.synopsis, &self.synopsis)
+            | fill(&mut record.published, &self.broadcast_date)
+            | fill(&mut record.duration, &duration)
+            | fill(&mut record.page_url, &self.page_url)
+            | fill(&mut record.image_url, &self.image_url)
+    }
+}
+
+/// The bbc.co.uk page of programme `pid`.
+pub fn programme_url(pid: &str) -> String {
+    format!("{}{}", PROGRAMMES_URL, pid)
+}
+
+/// Every episode linked from a listing page, in page order. Each quality
+/// of an episode is a separate entry.
+pub fn parse_listing(document: &Html, selectors: &Selectors) -> Vec<Episode> {
+    let Ok(selector) = Selector::parse(&selectors.episode) else {
+        return Vec::new();
+    };
+    document
+        .select(&selector)
+        .filter_map(|link| Episode::from_link(link, selectors))
+        .collect()
+}
+
+/// The outermost ancestor of `link` that has no download link of another
+/// episode: the episode's card on the listing page.
+fn card<'a>(link: ElementRef<'a>, selectors: &Selectors) -> Option<ElementRef<'a>> {
+    let episode = Selector::parse(&selectors.episode).ok()?;
+    let vpid_of = |link: ElementRef<'a>| -> Option<&'a str> {
+        link.value()
+            .attr(&selectors.url_attribute)
+            .and_then(crate::index::vpid_from_url)
+    };
+    let vpid = vpid_of(link);
+
+    link.ancestors()
+        .filter_map(ElementRef::wrap)
+        .take_while(|ancestor| {
+            ancestor.value().name() != "html"
+                && ancestor
+                    .select(&episode)
+                    .all(|other| vpid_of(other) == vpid)
+        })
+        .last()
+}
+
+/// The whitespace-normalised text of the first `selector` match in `card`.
+fn text_of(card: ElementRef, selector: &str) -> Option<String> {
+    let selector = Selector::parse(selector).ok()?;
+    card.select(&selector).next().and_then(text)
+}
+
+/// The whitespace-normalised text of `element`, if any.
+pub fn text(element: ElementRef) -> Option<String> {
+    let text = element.text().collect::<Vec<_>>().join(" ");
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// The first link in `card` to an episode page.
+fn page_url(card: ElementRef, selectors: &Selectors) -> Option<String> {
+    let selector = Selector::parse(&selectors.page_link).ok()?;
+    card.select(&selector)
+        .filter_map(|a| a.value().attr("href"))
+        .find(|href| pid_from_page_url(href).is_some())
+        .map(absolute)
+}
+
+/// The programme id in a bbc.co.uk `/programmes/<pid>` URL.
+pub fn pid_from_page_url(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("/programmes/")?;
+    let pid = rest.split(['/', '?', '#']).next()?;
+    is_pid(pid).then(|| pid.to_string())
+}
+
+/// The first broadcast date among the `selector` matches in `card`, from
+/// a machine-readable attribute if there is one, otherwise from text such
+/// as `17 Jul 2025`.
+fn broadcast_date(card: ElementRef, selector: &str) -> Option<NaiveDate> {
+    let selector = Selector::parse(selector).ok()?;
+    card.select(&selector).find_map(|element| {
+        let value = element.value();
+        if let Some(value) = value.attr("datetime").or_else(|| value.attr("content")) {
+            return NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok();
+        }
+        let text = text(element)?;
+        ["%d %b %Y", "%d %B %Y", "%a %d %b %Y", "%A %d %B %Y"]
+            .iter()
+            .find_map(|format| NaiveDate::parse_from_str(&text, format).ok())
+    })
+}
+
+/// The running time among the `selector` matches in `card`, from an ISO
+/// 8601 `content` or `datetime` attribute or the visible text.
+fn duration(card: ElementRef, selector: &str) -> Option<Duration> {
+    let selector = Selector::parse(selector).ok()?;
+    card.select(&selector).find_map(|element| {
+        let value = element.value();
+        match value.attr("content").or_else(|| value.attr("datetime")) {
+            Some(value) => parse_duration(value),
+            None => parse_duration(&text(element)?),
+        }
+    })
+}
+
+/// Parses `PT6M30S`, `6 mins`, `6 minutes` or `06:30`.
+fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    if let Some(iso) = text.strip_prefix("PT") {
+        let mut seconds = 0;
+        let mut number = String::new();
+        for c in iso.chars() {
+            match c {
+                '0'..='9' => number.push(c),
+                'H' | 'M' | 'S' => {
+                    let n = number.parse::<u64>().ok()?;
+                    seconds += n * match c {
+                        'H' => 3600,
+                        'M' => 60,
+                        _ => 1,
+                    };
+                    number.clear();
+                }
+                _ => return None,
+            }
+        }
+        return Some(Duration::from_secs(seconds));
+    }
+
+    if let Some((minutes, seconds)) = text.split_once(':') {
+        let minutes = minutes.trim().parse::<u64>().ok()?;
+        let seconds = seconds.trim().parse::<u64>().ok()?;
+        return Some(Duration::from_secs(minutes * 60 + seconds));
+    }
+
+    let (number, unit) = text.split_once(char::is_whitespace)?;
+    let number = number.parse::<u64>().ok()?;
+    match unit.trim().trim_end_matches('.') {
+        "min" | "mins" | "minute" | "minutes" => Some(Duration::from_secs(number * 60)),
+        "sec" | "secs" | "second" | "seconds" => Some(Duration::from_secs(number)),
+        _ => None,
+    }
+}
+
+/// `url` with a scheme; BBC pages link media protocol-relative (`//...`).
+pub(crate) fn absolute(url: &str) -> String {
+    match url.strip_prefix("//") {
+        Some(rest) => format!("https://{}", rest),
+        None => url.to_string(),
+    }
+}
+
+/// Splits a BBC download name such as
+/// `6 Minute English, A vaccine for cancer - p0ktxjlj.mp3` into the
+/// episode title and programme id.
+fn parse_download_name(download: &str) -> (Option<String>, Option<String>) {
+    let name = download.strip_suffix(".mp3").unwrap_or(download);
+    let name = name.split_once(", ").map_or(name, |(_, rest)| rest);
+    match name.rsplit_once(" - ") {
+        Some((title, pid)) if is_pid(pid) => (Some(title.to_string()), Some(pid.to_string())),
+        _ => (Some(name.to_string()), None),
+    }
+}
+
+/// BBC programme ids are eight lowercase alphanumerics starting with `p`.
+pub fn is_pid(s: &str) -> bool {
+    s.len() == 8
+        && s.starts_with('p')
+        && s.bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two episode cards as the downloads page lays them out, each with a
+    /// high and a low link.
+    const LISTING: &str = r#"<html><body><ol>
+<li><div class="programme" data-pid="p0l4xq77">
+  <img src="//ichef.bbci.co.uk/images/ic/480x270/p0l4xqb0.jpg">
+  <h2><a href="https://www.bbc.co.uk/programmes/p0l4xq77">
+    <span class="programme__title">Can AI solve crime?</span></a></h2>
+  <p class="programme__synopsis">Neil and Beth talk about
+    AI and policing.</p>
+  <span class="broadcast-event__date">17 Jul 2025</span>
+  <span class="programme__duration" property="duration" content="PT6M10S">6 mins</span>
+  <a href="//open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download/proto/https/vpid/p0lp7521.mp3"
+     download="6 Minute English, Can AI solve crime_ - p0l4xq77.mp3">Higher quality</a>
+  <a href="//open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download-low/proto/https/vpid/p0lp7521.mp3"
+     download="6 Minute English, Can AI solve crime_ - p0l4xq77.mp3">Lower quality</a>
+</div></li>
+<li><div class="programme">
+  <h2><a href="/programmes/p0l1abcd"><span class="programme__title">Is tea good for you?</span></a></h2>
+  <time datetime="2025-07-10">10 July 2025</time>
+  <span class="duration">06:30</span>
+  <a href="//open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download/proto/https/vpid/p0lm9999.mp3">Higher quality</a>
+  <a href="//open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download-low/proto/https/vpid/p0lm9999.mp3">Lower quality</a>
+</div></li>
+</ol></body></html>"#;
+
+    #[test]
+    fn listing_yields_both_variants_of_each_card() {
+        let document = Html::parse_document(LISTING);
+        let episodes = parse_listing(&document, &Selectors::default());
+        assert_eq!(
+            episodes
+                .iter()
+                .map(|episode| (episode.vpid.as_str(), episode.variant))
+                .collect::<Vec<_>>(),
+            [
+                ("p0lp7521", Variant::High),
+                ("p0lp7521", Variant::Low),
+                ("p0lm9999", Variant::High),
+                ("p0lm9999", Variant::Low),
+            ]
+        );
+
+        let first = &episodes[0];
+        assert_eq!(first.pid.as_deref(), Some("p0l4xq77"));
+        assert_eq!(first.title, "Can AI solve crime?");
+        assert_eq!(
+            first.synopsis.as_deref(),
+            Some("Neil and Beth talk about AI and policing.")
+        );
+        assert_eq!(first.broadcast_date, NaiveDate::from_ymd_opt(2025, 7, 17));
+        assert_eq!(first.duration, Some(Duration::from_secs(370)));
+        assert_eq!(
+            first.page_url.as_deref(),
+            Some("https://www.bbc.co.uk/programmes/p0l4xq77")
+        );
+        assert!(
+            first
+                .audio_url()
+                .starts_with("https://open.live.bbc.co.uk/")
+        );
+        assert_eq!(episodes[1].pid, first.pid);
+        assert_eq!(episodes[1].synopsis, first.synopsis);
+
+        let second = &episodes[2];
+        assert_eq!(second.pid.as_deref(), Some("p0l1abcd"));
+        assert_eq!(second.title, "Is tea good for you?");
+        assert_eq!(second.synopsis, None);
+        assert_eq!(second.broadcast_date, NaiveDate::from_ymd_opt(2025, 7, 10));
+        assert_eq!(second.duration, Some(Duration::from_secs(390)));
+    }
+
+    #[test]
+    fn durations_in_every_listed_form() {
+        assert_eq!(parse_duration("PT6M30S"), Some(Duration::from_secs(390)));
+        assert_eq!(parse_duration("PT1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("6 mins"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_duration("6 minutes"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_duration("06:30"), Some(Duration::from_secs(390)));
+        assert_eq!(parse_duration("soon"), None);
+    }
+
+    #[test]
+    fn absolute_adds_a_scheme_to_protocol_relative_urls() {
+        assert_eq!(
+            absolute("//ichef.bbci.co.uk/a.jpg"),
+            "https://ichef.bbci.co.uk/a.jpg"
+        );
+        assert_eq!(absolute("http://example.com/a"), "http://example.com/a");
+    }
+
+    #[test]
+    fn download_name_splits_title_and_pid() {
+        assert_eq!(
+            parse_download_name("6 Minute English, A vaccine for cancer - p0ktxjlj.mp3"),
+            (
+                Some("A vaccine for cancer".to_string()),
+                Some("p0ktxjlj".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn download_name_keeps_commas_in_title() {
+        assert_eq!(
+            parse_download_name("6 Minute Grammar, 'Can', 'could' and 'be able to' - p0c4x2lz.mp3"),
+            (
+                Some("'Can', 'could' and 'be able to'".to_string()),
+                Some("p0c4x2lz".to_string())
+            )
+        );
+        assert_eq!(
+            parse_download_name("6 Minute English, Yes, no, maybe - p0abc123.mp3"),
+            (
+                Some("Yes, no, maybe".to_string()),
+                Some("p0abc123".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn download_name_without_pid() {
+        assert_eq!(
+            parse_download_name("6 Minute English, Episode 1 - extra.mp3"),
+            (Some("Episode 1 - extra".to_string()), None)
+        );
+        assert_eq!(
+            parse_download_name("Untitled"),
+            (Some("Untitled".to_string()), None)
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use std::{cmp::Reverse, fs};
+
+use chrono::NaiveDate;
+
+use crate::{artwork, config::PodcastConfig, index::Index, markup::escape, mime};
+
+/// Name of the feed file written into each series download folder.
+pub const FEED_FILE_NAME: &str = "feed.xml";
+
+/// The `<channel>` of a series feed.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub title: String,
+    /// The series page on bbc.co.uk.
+    pub link: String,
+    pub description: String,
+    pub author: String,
+    /// Where the feed itself is published.
+    pub feed_url: String,
+    pub image_url: Option<String>,
+}
+
+/// One episode with a local file.
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub title: String,
+    pub description: Option<String>,
+    pub published: NaiveDate,
+    /// The episode's vpid.
+    pub guid: String,
+    pub link: Option<String>,
+    pub enclosure_url: String,
+    /// Byte size of the file.
+    pub length: u64,
+    pub mime_type: &'static str,
+    /// Running time in seconds.
+    pub duration: Option<u64>,
+    pub image_url: Option<String>,
+}
+
+/// The feed of a series whose download folder is published at
+/// `series_url`: its downloaded episodes that are on disk, newest first.
+pub fn for_series(
+    podcast: &PodcastConfig,
+    index: &Index,
+    series_url: &str,
+) -> (Channel, Vec<Item>) {
+    let folder = &podcast.download_folder;
+    let series_url = series_url.trim_end_matches('/');
+    let url_of = |path: &str| format!("{}/{}", series_url, encode_path(path));
+
+    let mut records = index.episodes().collect::<Vec<_>>();
+    records.sort_by_key(|record| Reverse(record.publish_order()));
+
+    let mut items = Vec::new();
+    for record in records {
+        let Some(filename) = &record.filename else {
+            continue;
+        };
+        let Ok(metadata) = fs::metadata(folder.join(filename)) else {
+            continue;
+        };
+        items.push(Item {
+            title: record.title.clone().unwrap_or_else(|| record.vpid.clone()),
+            description: record.synopsis.clone(),
+            published: record.date(),
+            guid: record.vpid.clone(),
+            link: record.page_url.clone(),
+            enclosure_url: url_of(filename),
+            length: metadata.len(),
+            mime_type: mime::of(filename),
+            duration: record.duration,
+            image_url: record.image_url.clone(),
+        });
+    }
+
+    let channel = Channel {
+        title: podcast.name.clone(),
+        link: podcast.url.clone(),
+        description: format!("{} by {}", podcast.name, podcast.artist),
+        author: podcast.artist.clone(),
+        feed_url: url_of(FEED_FILE_NAME),
+        image_url: artwork::cached_series_image(folder).map(|path| url_of(&path)),
+    };
+    (channel, items)
+}
+
+/// An RSS 2.0 document with the iTunes podcast extensions.
+pub fn render(channel: &Channel, items: &[Item]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(
+        "<rss version=\"2.0\" \
+         xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\" \
+         xmlns:atom=\"http://www.w3.org/2005/Atom\">\n",
+    );
+    xml.push_str("<channel>\n");
+    element(&mut xml, 1, "title", &channel.title);
+    element(&mut xml, 1, "link", &channel.link);
+    element(&mut xml, 1, "description", &channel.description);
+    element(&mut xml, 1, "language", "en-gb");
+    xml.push_str(&format!(
+        "  <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape(&channel.feed_url)
+    ));
+    element(&mut xml, 1, "itunes:author", &channel.author);
+    xml.push_str("  <itunes:category text=\"Education\">\n");
+    xml.push_str("    <itunes:category text=\"Language Learning\"/>\n");
+    xml.push_str("  </itunes:category>\n");
+    element(&mut xml, 1, "itunes:explicit", "false");
+    if let Some(image_url) = &channel.image_url {
+        xml.push_str("  <image>\n");
+        element(&mut xml, 2, "url", image_url);
+        element(&mut xml, 2, "title", &channel.title);
+        element(&mut xml, 2, "link", &channel.link);
+        xml.push_str("  </image>\n");
+        xml.push_str(&format!(
+            "  <itunes:image href=\"{}\"/>\n",
+            escape(image_url)
+        ));
+    }
+
+    for item in items {
+        xml.push_str("  <item>\n");
+        element(&mut xml, 2, "title", &item.title);
+        if let Some(description) = &item.description {
+            element(&mut xml, 2, "description", description);
+            element(&mut xml, 2, "itunes:summary", description);
+        }
+        if let Some(link) = &item.link {
+            element(&mut xml, 2, "link", link);
+        }
+        xml.push_str(&format!(
+            "    <guid isPermaLink=\"false\">{}</guid>\n",
+            escape(&item.guid)
+        ));
+        element(&mut xml, 2, "pubDate", &rfc2822(item.published));
+        xml.push_str(&format!(
+            "    <enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+            escape(&item.enclosure_url),
+            item.length,
+            item.mime_type
+        ));
+        if let Some(duration) = item.duration {
+            element(&mut xml, 2, "itunes:duration", &duration.to_string());
+        }
+        if let Some(image_url) = &item.image_url {
+            xml.push_str(&format!(
+                "    <itunes:image href=\"{}\"/>\n",
+                escape(image_url)
+            ));
+        }
+        element(&mut xml, 2, "itunes:episodeType", "full");
+        xml.push_str("  </item>\n");
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn element(xml: &mut String, depth: usize, name: &str, text: &str) {
+    xml.push_str(&format!(
+        "{}<{}>{}</{}>\n",
+        "  ".repeat(depth),
+        name,
+        escape(text),
+        name
+    ));
+}
+
+/// Midnight UTC of `date`, as RSS wants it.
+fn rfc2822(date: NaiveDate) -> String {
+    date.format("%a, %d %b %Y 00:00:00 +0000").to_string()
+}
+
+/// A relative `/`-separated path with each segment percent-encoded.
+pub fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(encode_path_segment)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `segment` percent-encoded for use in a URL path.
+fn encode_path_segment(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    const FILE: &str = "Can_AI_solve_crime_-_p0l4xq77.mp3";
+
+    #[test]
+    fn enclosures_are_under_the_download_folder_name() {
+        let root = tempfile::tempdir().unwrap();
+        let folder = root.path().join("6min_english");
+        fs::create_dir(&folder).unwrap();
+        fs::write(folder.join(FILE), [0; 1234]).unwrap();
+        let config = format!(
+            "[[podcast]]\nname = \"6 Minute English\"\nurl = \"https://www.bbc.co.uk/programmes/p02pc9tn/episodes/downloads\"\ndownload_folder = {:?}\n",
+            folder
+        )
+        .parse::<Config>()
+        .unwrap();
+        let podcast = &config.podcasts[0];
+        let mut index = Index::open(&folder, &podcast.name).unwrap();
+        index
+            .insert(
+                serde_json::from_str(&format!(
+                    r#"{{"vpid":"p0lp7521","pid":"p0l4xq77","title":"Can AI solve crime?","series":"6 Minute English","url":"//open.live.bbc.co.uk/vpid/p0lp7521.mp3","filename":"{}","published":"2025-07-17","downloaded_at":"2025-07-18T08:00:00+01:00"}}"#,
+                    FILE
+                ))
+                .unwrap(),
+            )
+            .unwrap();
+
+        let series_url = format!(
+            "https://podcasts.example.com/{}",
+            encode_path(&podcast.folder_name())
+        );
+        let (channel, items) = for_series(podcast, &index, &series_url);
+        assert_eq!(
+            channel.feed_url,
+            "https://podcasts.example.com/6min_english/feed.xml"
+        );
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].enclosure_url,
+            format!("https://podcasts.example.com/6min_english/{}", FILE)
+        );
+        assert_eq!(items[0].length, 1234);
+        assert_eq!(items[0].mime_type, "audio/mpeg");
+    }
+
+    #[test]
+    fn renders_enclosure_and_escapes_text() {
+        let channel = Channel {
+            title: "6 Minute English".to_string(),
+            link: "https://www.bbc.co.uk/programmes/p02pc9tn".to_string(),
+            description: "6 Minute English by BBC Learning English".to_string(),
+            author: "BBC Learning English".to_string(),
+            feed_url: "https://podcasts.example.com/6min_english/feed.xml".to_string(),
+            image_url: None,
+        };
+        let item = Item {
+            title: "Q&A: <Why>?".to_string(),
+            description: None,
+            published: NaiveDate::from_ymd_opt(2025, 7, 17).unwrap(),
+            guid: "p0lp7521".to_string(),
+            link: None,
+            enclosure_url: format!(
+                "https://podcasts.example.com/6min_english/{}",
+                encode_path("Q&A - p0l4xq77.mp3")
+            ),
+            length: 1234,
+            mime_type: "audio/mpeg",
+            duration: Some(370),
+            image_url: None,
+        };
+
+        let xml = render(&channel, &[item]);
+        assert!(xml.contains(
+            "<enclosure url=\"https://podcasts.example.com/6min_english/Q%26A%20-%20p0l4xq77.mp3\" \
+             length=\"1234\" type=\"audio/mpeg\"/>"
+        ));
+        assert!(xml.contains("<title>Q&amp;A: &lt;Why&gt;?</title>"));
+        assert!(xml.contains("<guid isPermaLink=\"false\">p0lp7521</guid>"));
+        assert!(xml.contains("<pubDate>Thu, 17 Jul 2025 00:00:00 +0000</pubDate>"));
+        assert!(xml.contains("<itunes:duration>370</itunes:duration>"));
+        assert!(roxmltree::Document::parse(&xml).is_ok());
+    }
+}
@@ -0,0 +1,464 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use reqwest::{
+    Client, Response, StatusCode,
+    header::{
+        CONTENT_RANGE, CONTENT_TYPE, ETAG, HeaderMap, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE,
+        LAST_MODIFIED, RANGE,
+    },
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+use crate::index;
+
+/// The `ETag` and `Last-Modified` of a response, to ask the server later
+/// whether it changed. Those of the response a `.part` file was started
+/// from are kept next to it.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        }
+    }
+
+    /// The value to send as `If-Range`, preferring the strong ETag.
+    fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// The body of `url` as text, failing on error statuses.
+pub async fn get_text(client: &Client, url: &str) -> io::Result<String> {
+    client
+        .get(url)
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .map_err(io::Error::other)?
+        .text()
+        .await
+        .map_err(io::Error::other)
+}
+
+/// Like `get_text`, but conditional on `validators` from an earlier
+/// response: `None` when the server answers 304 Not Modified, else the
+/// body with its own validators.
+pub async fn get_text_if_modified(
+    client: &Client,
+    url: &str,
+    validators: &Validators,
+) -> io::Result<Option<(String, Validators)>> {
+    let mut request = client.get(url);
+    if let Some(etag) = &validators.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let response = request.send().await.map_err(io::Error::other)?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let response = response.error_for_status().map_err(io::Error::other)?;
+    let validators = Validators::from_headers(response.headers());
+    let body = response.text().await.map_err(io::Error::other)?;
+    Ok(Some((body, validators)))
+}
+
+/// Streams `url` into `<path>.part`, fsyncs it and renames it over `path`
+/// once complete, returning the byte size and SHA-256.
+///
+/// An existing `.part` file is resumed with a `Range` request when its
+/// validators are known; if the server ignores the range or the file has
+/// changed, the download starts again from zero.
+///
+/// `on_progress` is called with the bytes on disk so far and the expected
+/// total, when the server sent a length.
+pub async fn download_file(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    on_progress: impl Fn(u64, Option<u64>),
+) -> io::Result<(u64, String)> {
+    let part = part_path(path);
+    let meta = meta_path(&part);
+
+    let mut resume = match fs::metadata(&part).await {
+        Ok(metadata) if metadata.len() > 0 => read_validators(&meta)
+            .await
+            .filter(|validators| validators.if_range().is_some())
+            .map(|validators| (metadata.len(), validators)),
+        _ => None,
+    };
+
+    let mut response = loop {
+        let mut request = client.get(url);
+        if let Some((offset, validators)) = &resume {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+            if let Some(if_range) = validators.if_range() {
+                request = request.header(IF_RANGE, if_range);
+            }
+        }
+
+        let response = request.send().await.map_err(io::Error::other)?;
+        if resume.is_some() && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            resume = None;
+            continue;
+        }
+        let response = response.error_for_status().map_err(io::Error::other)?;
+
+        match &resume {
+            Some((offset, validators))
+                if response.status() == StatusCode::PARTIAL_CONTENT
+                    && !(range_start(&response) == Some(*offset)
+                        && Validators::from_headers(response.headers()) == *validators) =>
+            {
+                // The server honoured the range but not `If-Range`, and the
+                // file is no longer the one we started.
+                resume = None;
+            }
+            _ => break response,
+        }
+    };
+
+    check_content_type(&response)?;
+
+    let resuming = resume.is_some() && response.status() == StatusCode::PARTIAL_CONTENT;
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+
+    let mut file = if resuming {
+        size = hash_file(&part, &mut hasher).await?;
+        OpenOptions::new().append(true).open(&part).await?
+    } else {
+        let validators = Validators::from_headers(response.headers());
+        if validators.is_empty() {
+            let _ = fs::remove_file(&meta).await;
+        } else {
+            fs::write(&meta, serde_json::to_vec(&validators)?).await?;
+        }
+        File::create(&part).await?
+    };
+    let expected = response.content_length().map(|length| size + length);
+    on_progress(size, expected);
+
+    while let Some(chunk) = response.chunk().await.map_err(io::Error::other)? {
+        file.write_all(&chunk).await?;
+        hasher.update(&chunk);
+        size += chunk.len() as u64;
+        on_progress(size, expected);
+    }
+
+    if let Some(expected) = expected
+        && expected != size
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("got {} of {} bytes", size, expected),
+        ));
+    }
+
+    file.sync_all().await?;
+    drop(file);
+    fs::rename(&part, path).await?;
+    let _ = fs::remove_file(&meta).await;
+
+    Ok((size, index::to_hex(&hasher.finalize())))
+}
+
+/// Rejects responses that are clearly not audio, such as an HTML error
+/// page served with a 200 status.
+fn check_content_type(response: &Response) -> io::Result<()> {
+    let Some(content_type) = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Ok(());
+    };
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    if mime.starts_with("audio/") || mime.ends_with("/octet-stream") {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected content type {}", mime),
+        ))
+    }
+}
+
+/// `<path>.part`, where an in-progress download is written.
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// `<part>.meta`, holding the validators of the partial download.
+fn meta_path(part: &Path) -> PathBuf {
+    let mut meta = part.as_os_str().to_owned();
+    meta.push(".meta");
+    PathBuf::from(meta)
+}
+
+async fn read_validators(meta: &Path) -> Option<Validators> {
+    let content = fs::read(meta).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// The first byte position of a `Content-Range: bytes <start>-<end>/<len>`.
+fn range_start(response: &Response) -> Option<u64> {
+    let range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let (start, _) = range.strip_prefix("bytes ")?.split_once('-')?;
+    start.parse().ok()
+}
+
+/// Feeds the bytes already in `path` into `hasher`, returning their count.
+async fn hash_file(path: &Path, hasher: &mut Sha256) -> io::Result<u64> {
+    let mut file = File::open(path).await?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(size);
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        Router,
+        extract::State,
+        http::{HeaderMap, StatusCode, header},
+        response::{IntoResponse, Response},
+        routing::get,
+    };
+
+    use super::*;
+
+    const ETAG_V1: &str = "\"v1\"";
+
+    /// The requests the stub server saw, as (Range, If-Range).
+    type Seen = Arc<Mutex<Vec<(Option<String>, Option<String>)>>>;
+
+    fn body() -> Vec<u8> {
+        (0..1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn record(seen: &Seen, headers: &HeaderMap) {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        seen.lock()
+            .unwrap()
+            .push((header(header::RANGE), header(header::IF_RANGE)));
+    }
+
+    /// Serves `body()` with ETag `"v1"`, honouring `Range` when `If-Range`
+    /// matches.
+    async fn audio(State(seen): State<Seen>, headers: HeaderMap) -> Response {
+        record(&seen, &headers);
+        let body = body();
+        let start = headers
+            .get(header::RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|range| range.strip_prefix("bytes="))
+            .and_then(|range| range.strip_suffix('-'))
+            .and_then(|start| start.parse::<usize>().ok())
+            .filter(|_| {
+                headers
+                    .get(header::IF_RANGE)
+                    .is_none_or(|if_range| if_range == ETAG_V1)
+            });
+        match start {
+            Some(start) => (
+                StatusCode::PARTIAL_CONTENT,
+                [
+                    (header::CONTENT_TYPE, "audio/mpeg".to_string()),
+                    (header::ETAG, ETAG_V1.to_string()),
+                    (
+                        header::CONTENT_RANGE,
+                        format!("bytes {}-{}/{}", start, body.len() - 1, body.len()),
+                    ),
+                ],
+                body[start..].to_vec(),
+            )
+                .into_response(),
+            None => full(body),
+        }
+    }
+
+    /// Serves `body()` whole, whatever the request asks for.
+    async fn ignores_range(State(seen): State<Seen>, headers: HeaderMap) -> Response {
+        record(&seen, &headers);
+        full(body())
+    }
+
+    async fn error_page() -> Response {
+        (
+            [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+            "<html><body>Not available in your region</body></html>",
+        )
+            .into_response()
+    }
+
+    fn full(body: Vec<u8>) -> Response {
+        (
+            [
+                (header::CONTENT_TYPE, "audio/mpeg"),
+                (header::ETAG, ETAG_V1),
+            ],
+            body,
+        )
+            .into_response()
+    }
+
+    /// Starts the stub server, returning its base URL and request log.
+    async fn stub() -> (String, Seen) {
+        let seen = Seen::default();
+        let app = Router::new()
+            .route("/audio.mp3", get(audio))
+            .route("/ignores-range.mp3", get(ignores_range))
+            .route("/error.mp3", get(error_page))
+            .with_state(seen.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{}", address), seen)
+    }
+
+    /// A `.part` file of `content` next to `path`, started from a response
+    /// with `etag`.
+    fn partial(path: &Path, content: &[u8], etag: &str) {
+        std::fs::write(part_path(path), content).unwrap();
+        std::fs::write(
+            meta_path(&part_path(path)),
+            format!("{{\"etag\":{:?}}}", etag),
+        )
+        .unwrap();
+    }
+
+    async fn download(url: &str, path: &Path) -> io::Result<(u64, String)> {
+        download_file(&Client::new(), url, path, |_, _| {}).await
+    }
+
+    #[tokio::test]
+    async fn downloads_into_part_and_renames() {
+        let (base, seen) = stub().await;
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("episode.mp3");
+
+        let (size, sha256) = download(&format!("{}/audio.mp3", base), &path)
+            .await
+            .unwrap();
+        assert_eq!(size, 1000);
+        assert_eq!(sha256, index::sha256_hex(&body()));
+        assert_eq!(std::fs::read(&path).unwrap(), body());
+        assert!(!part_path(&path).exists());
+        assert!(!meta_path(&part_path(&path)).exists());
+        assert_eq!(*seen.lock().unwrap(), [(None, None)]);
+    }
+
+    #[tokio::test]
+    async fn resumes_with_if_range() {
+        let (base, seen) = stub().await;
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("episode.mp3");
+        partial(&path, &body()[..400], ETAG_V1);
+
+        let (size, sha256) = download(&format!("{}/audio.mp3", base), &path)
+            .await
+            .unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [(Some("bytes=400-".to_string()), Some(ETAG_V1.to_string()))]
+        );
+        assert_eq!(size, 1000);
+        assert_eq!(sha256, index::sha256_hex(&body()));
+        assert_eq!(std::fs::read(&path).unwrap(), body());
+        assert!(!meta_path(&part_path(&path)).exists());
+    }
+
+    #[tokio::test]
+    async fn restarts_when_the_file_changed() {
+        let (base, _) = stub().await;
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("episode.mp3");
+        partial(&path, &[0xff; 400], "\"v0\"");
+
+        let (size, _) = download(&format!("{}/audio.mp3", base), &path)
+            .await
+            .unwrap();
+        assert_eq!(size, 1000);
+        assert_eq!(std::fs::read(&path).unwrap(), body());
+    }
+
+    #[tokio::test]
+    async fn restarts_on_200_instead_of_206() {
+        let (base, seen) = stub().await;
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("episode.mp3");
+        partial(&path, &[0xff; 400], ETAG_V1);
+
+        let (size, sha256) = download(&format!("{}/ignores-range.mp3", base), &path)
+            .await
+            .unwrap();
+        assert_eq!(seen.lock().unwrap()[0].0.as_deref(), Some("bytes=400-"));
+        assert_eq!(size, 1000);
+        assert_eq!(sha256, index::sha256_hex(&body()));
+        assert_eq!(std::fs::read(&path).unwrap(), body());
+    }
+
+    #[tokio::test]
+    async fn rejects_non_audio_content() {
+        let (base, _) = stub().await;
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("episode.mp3");
+
+        let e = download(&format!("{}/error.mp3", base), &path)
+            .await
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("text/html"));
+        assert!(!path.exists());
+        assert!(!part_path(&path).exists());
+    }
+}
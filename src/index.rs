@@ -0,0 +1,374 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::{episode::Variant, vocabulary::VocabularyEntry};
+
+const INDEX_FILE_NAME: &str = ".podcast_index.jsonl";
+const LEGACY_INDEX_FILE_NAME: &str = ".podcast_index";
+const MIGRATED_SUFFIX: &str = "migrated";
+
+/// One downloaded episode, as stored in the series index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpisodeRecord {
+    /// Media id of the audio file; with the variant, the key of the
+    /// index.
+    pub vpid: String,
+    /// Programme id of the episode page.
+    pub pid: Option<String>,
+    pub title: Option<String>,
+    #[serde(default, alias = "description")]
+    pub synopsis: Option<String>,
+    /// Running time in seconds, as listed.
+    #[serde(default)]
+    pub duration: Option<u64>,
+    /// The episode page on bbc.co.uk.
+    #[serde(default)]
+    pub page_url: Option<String>,
+    /// Programme image of the episode, embedded as cover art.
+    #[serde(default)]
+    pub image_url: Option<String>,
+    pub series: String,
+    pub url: String,
+    /// Which encoding `url` is; older records leave it to the URL.
+    #[serde(default)]
+    pub variant: Option<Variant>,
+    /// Path relative to the series download folder.
+    pub filename: Option<String>,
+    pub size: Option<u64>,
+    /// Hex-encoded SHA-256 of the file.
+    pub sha256: Option<String>,
+    /// First broadcast date, from the listing.
+    pub published: Option<NaiveDate>,
+    /// When the file was downloaded, or last attempted if it failed.
+    pub downloaded_at: DateTime<Local>,
+    /// Transcript file, relative to the series download folder.
+    #[serde(default)]
+    pub transcript: Option<String>,
+    /// PDF version of the transcript, relative to the download folder.
+    #[serde(default)]
+    pub transcript_pdf: Option<String>,
+    /// The Learning English page the transcript came from.
+    #[serde(default)]
+    pub transcript_url: Option<String>,
+    /// Terms from the transcript's "Vocabulary" section.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vocabulary: Vec<VocabularyEntry>,
+    /// Set when the download failed permanently; such episodes are only
+    /// retried with `sync --retry-failed`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+}
+
+impl EpisodeRecord {
+    pub fn is_downloaded(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant.unwrap_or_else(|| Variant::of_url(&self.url))
+    }
+
+    fn key(&self) -> (String, Variant) {
+        (self.vpid.clone(), self.variant())
+    }
+
+    /// Takes the transcript of another variant of the same episode.
+    pub fn copy_transcript(&mut self, other: &EpisodeRecord) {
+        self.transcript = other.transcript.clone();
+        self.transcript_pdf = other.transcript_pdf.clone();
+        self.transcript_url = other.transcript_url.clone();
+        self.vocabulary = other.vocabulary.clone();
+    }
+
+    /// Whether `name`, a file in the series folder, has this episode's
+    /// programme id or media id in it.
+    pub fn names_file(&self, name: &str) -> bool {
+        self.pid.as_deref().is_some_and(|pid| name.contains(pid))
+            || name.contains(self.vpid.as_str())
+    }
+
+    /// The publish date, or the download date when it is not known.
+    pub fn date(&self) -> NaiveDate {
+        self.published
+            .unwrap_or_else(|| self.downloaded_at.date_naive())
+    }
+
+    /// Sort key putting episodes in the order they were published.
+    pub fn publish_order(&self) -> (NaiveDate, DateTime<Local>) {
+        (self.date(), self.downloaded_at)
+    }
+}
+
+/// The per-series episode database: a JSON-lines file where later lines
+/// for the same vpid and variant replace earlier ones.
+#[derive(Debug)]
+pub struct Index {
+    path: PathBuf,
+    records: Vec<EpisodeRecord>,
+    by_key: HashMap<(String, Variant), usize>,
+}
+
+impl Index {
+    /// Opens the index in `folder`, migrating a legacy plain-text
+    /// `.podcast_index` on first use.
+    pub fn open(folder: &Path, series: &str) -> io::Result<Self> {
+        let path = folder.join(INDEX_FILE_NAME);
+        let mut index = Self {
+            path,
+            records: Vec::new(),
+            by_key: HashMap::new(),
+        };
+
+        let legacy = folder.join(LEGACY_INDEX_FILE_NAME);
+        if !index.path.exists() && legacy.exists() {
+            index.migrate_legacy(&legacy, series)?;
+            return Ok(index);
+        }
+
+        match File::open(&index.path) {
+            Ok(file) => {
+                for (number, line) in BufReader::new(file).lines().enumerate() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let record = serde_json::from_str(&line).map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{}:{}: {}", index.path.display(), number + 1, e),
+                        )
+                    })?;
+                    index.upsert(record);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(index)
+    }
+
+    /// The JSON-lines file, which need not exist yet.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, vpid: &str, variant: Variant) -> Option<&EpisodeRecord> {
+        self.by_key
+            .get(&(vpid.to_string(), variant))
+            .map(|&i| &self.records[i])
+    }
+
+    /// The records of every variant of `vpid`, high first.
+    pub fn variants(&self, vpid: &str) -> impl Iterator<Item = &EpisodeRecord> {
+        [Variant::High, Variant::Low]
+            .into_iter()
+            .filter_map(move |variant| self.get(vpid, variant))
+    }
+
+    /// Replaces records in memory only; call `compact` to persist them.
+    pub fn update(&mut self, records: impl IntoIterator<Item = EpisodeRecord>) {
+        for record in records {
+            self.upsert(record);
+        }
+    }
+
+    /// Records of the episodes that are on disk, skipping failures.
+    pub fn downloaded(&self) -> impl Iterator<Item = &EpisodeRecord> {
+        self.records.iter().filter(|record| record.is_downloaded())
+    }
+
+    /// One downloaded record per episode, the high-quality one when both
+    /// variants are on disk.
+    pub fn episodes(&self) -> impl Iterator<Item = &EpisodeRecord> {
+        self.downloaded().filter(|record| {
+            record.variant() == Variant::High
+                || !self
+                    .get(&record.vpid, Variant::High)
+                    .is_some_and(EpisodeRecord::is_downloaded)
+        })
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &EpisodeRecord> {
+        self.records.iter().filter(|record| !record.is_downloaded())
+    }
+
+    /// 1-based position of `record` among the downloaded episodes in
+    /// publish order.
+    pub fn track_number(&self, record: &EpisodeRecord) -> u32 {
+        let earlier = self
+            .episodes()
+            .filter(|other| {
+                other.vpid != record.vpid && other.publish_order() < record.publish_order()
+            })
+            .count();
+        earlier as u32 + 1
+    }
+
+    pub fn last_download(&self) -> Option<DateTime<Local>> {
+        self.downloaded().map(|record| record.downloaded_at).max()
+    }
+
+    /// Adds or replaces the record for `record`'s vpid and variant and
+    /// appends it to the file.
+    pub fn insert(&mut self, record: EpisodeRecord) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        self.upsert(record);
+        Ok(())
+    }
+
+    /// Rewrites the file with one line per episode, replacing it
+    /// atomically.
+    pub fn compact(&self) -> io::Result<()> {
+        let tmp = self.path.with_extension("jsonl.tmp");
+        {
+            let mut file = File::create(&tmp)?;
+            for record in &self.records {
+                writeln!(file, "{}", serde_json::to_string(record)?)?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)
+    }
+
+    fn upsert(&mut self, record: EpisodeRecord) {
+        match self.by_key.get(&record.key()) {
+            Some(&i) => self.records[i] = record,
+            None => {
+                self.by_key.insert(record.key(), self.records.len());
+                self.records.push(record);
+            }
+        }
+    }
+
+    /// Converts `timestamp url` lines into records and moves the old file
+    /// out of the way.
+    fn migrate_legacy(&mut self, legacy: &Path, series: &str) -> io::Result<()> {
+        let content = fs::read_to_string(legacy)?;
+        for line in content.lines() {
+            let Some((timestamp, url)) = line.split_once(' ') else {
+                continue;
+            };
+            let Ok(timestamp) = NaiveDateTime::parse_from_str(timestamp, "%Y%m%d%H%M%S") else {
+                continue;
+            };
+            let Some(vpid) = vpid_from_url(url) else {
+                continue;
+            };
+            self.upsert(EpisodeRecord {
+                vpid: vpid.to_string(),
+                pid: None,
+                title: None,
+                synopsis: None,
+                duration: None,
+                page_url: None,
+                image_url: None,
+                series: series.to_string(),
+                url: url.to_string(),
+                variant: None,
+                filename: None,
+                size: None,
+                sha256: None,
+                published: None,
+                downloaded_at: Local
+                    .from_local_datetime(&timestamp)
+                    .earliest()
+                    .unwrap_or_else(Local::now),
+                transcript: None,
+                transcript_pdf: None,
+                transcript_url: None,
+                vocabulary: Vec::new(),
+                failure: None,
+            });
+        }
+
+        self.compact()?;
+        fs::rename(legacy, legacy.with_extension(MIGRATED_SUFFIX))?;
+        info!(
+            "Migrated {} entries from {} to {}",
+            self.records.len(),
+            legacy.display(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+/// The media id in a mediaselector URL, e.g. `p0lp7521` in
+/// `.../vpid/p0lp7521.mp3`.
+pub fn vpid_from_url(url: &str) -> Option<&str> {
+    url.rsplit('/')
+        .next()
+        .and_then(|file| file.strip_suffix(".mp3"))
+        .filter(|vpid| !vpid.is_empty())
+}
+
+/// Byte size and hex-encoded SHA-256 of the file at `path`.
+pub fn file_digest(path: &Path) -> io::Result<(u64, String)> {
+    let bytes = fs::read(path)?;
+    Ok((bytes.len() as u64, sha256_hex(&bytes)))
+}
+
+/// Hex-encoded SHA-256 of `bytes`.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(bytes))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGACY: &str = "\
+20240103071500 https://open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download/proto/https/vpid/p0lp7521.mp3
+not a line
+20240110071500 https://open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download-low/proto/https/vpid/p0lq1234.mp3
+";
+
+    #[test]
+    fn migrates_legacy_index_once() {
+        let folder = tempfile::tempdir().unwrap();
+        let legacy = folder.path().join(LEGACY_INDEX_FILE_NAME);
+        fs::write(&legacy, LEGACY).unwrap();
+
+        let index = Index::open(folder.path(), "6 Minute English").unwrap();
+        assert_eq!(index.episodes().count(), 2);
+        let record = index.get("p0lp7521", Variant::High).unwrap();
+        assert_eq!(record.series, "6 Minute English");
+        assert!(record.url.ends_with("/vpid/p0lp7521.mp3"));
+        assert_eq!(
+            record.downloaded_at.naive_local(),
+            NaiveDateTime::parse_from_str("20240103071500", "%Y%m%d%H%M%S").unwrap()
+        );
+        assert!(index.get("p0lq1234", Variant::Low).is_some());
+        assert!(!legacy.exists());
+        assert!(folder.path().join(".podcast_index.migrated").exists());
+
+        // The new index wins over a legacy file that shows up again.
+        fs::write(
+            &legacy,
+            "20240117071500 https://example.com/vpid/p0lr9999.mp3\n",
+        )
+        .unwrap();
+        let index = Index::open(folder.path(), "6 Minute English").unwrap();
+        assert_eq!(index.episodes().count(), 2);
+        assert!(index.get("p0lr9999", Variant::High).is_none());
+        assert!(legacy.exists());
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use reqwest::Url;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, SemaphorePermit};
+
+use crate::config::Limits;
+
+/// Download slots shared by every series of a run: a global pool and one
+/// per capped host.
+#[derive(Debug)]
+pub struct Limiter {
+    downloads: Semaphore,
+    per_host: Option<usize>,
+    host_limits: HashMap<String, usize>,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+/// Held for the length of a download.
+#[derive(Debug)]
+pub struct Permit<'a> {
+    _host: Option<OwnedSemaphorePermit>,
+    _download: SemaphorePermit<'a>,
+}
+
+impl Limiter {
+    pub fn new(limits: &Limits) -> Self {
+        Self {
+            downloads: Semaphore::new(limits.downloads),
+            per_host: limits.per_host,
+            host_limits: limits.hosts.clone(),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits for a slot to download `url`: the host's first, if it is
+    /// capped, then one of the run's. Every download takes them in this
+    /// order, so no task holds a global slot while waiting for its host.
+    pub async fn acquire(&self, url: &str) -> Permit<'_> {
+        let host = match self.host_semaphore(url) {
+            Some(semaphore) => Some(semaphore.acquire_owned().await.expect("never closed")),
+            None => None,
+        };
+        Permit {
+            _host: host,
+            _download: self.downloads.acquire().await.expect("never closed"),
+        }
+    }
+
+    fn host_semaphore(&self, url: &str) -> Option<Arc<Semaphore>> {
+        let url = Url::parse(url).ok()?;
+        let host = url.host_str()?.to_ascii_lowercase();
+        let limit = self.host_limits.get(&host).copied().or(self.per_host)?;
+        let mut hosts = self.hosts.lock().unwrap();
+        Some(Arc::clone(
+            hosts
+                .entry(host)
+                .or_insert_with(|| Arc::new(Semaphore::new(limit))),
+        ))
+    }
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self::new(&Limits::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use futures::future::join_all;
+
+    use super::*;
+
+    fn limiter() -> Limiter {
+        Limiter::new(&Limits {
+            downloads: 8,
+            per_host: Some(2),
+            hosts: HashMap::from([("cdn.example.com".to_string(), 1)]),
+        })
+    }
+
+    /// The most downloads of `urls` that held a permit at the same time.
+    async fn peak(limiter: &Limiter, urls: &[&str]) -> usize {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        join_all(urls.iter().map(|url| async {
+            let _permit = limiter.acquire(url).await;
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+        }))
+        .await;
+        peak.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn per_host_cap_limits_concurrency() {
+        let limiter = limiter();
+        let url = "https://open.live.bbc.co.uk/vpid/p0lp7521.mp3";
+        assert_eq!(peak(&limiter, &[url; 6]).await, 2);
+
+        let url = "https://CDN.example.com/ep1.mp3";
+        assert_eq!(peak(&limiter, &[url; 4]).await, 1);
+    }
+
+    #[tokio::test]
+    async fn hosts_are_capped_separately() {
+        let limiter = limiter();
+        let urls = [
+            "https://open.live.bbc.co.uk/vpid/a.mp3",
+            "https://open.live.bbc.co.uk/vpid/b.mp3",
+            "https://ichef.bbci.co.uk/images/c.jpg",
+            "https://ichef.bbci.co.uk/images/d.jpg",
+        ];
+        assert_eq!(peak(&limiter, &urls).await, 4);
+    }
+
+    #[tokio::test]
+    async fn global_cap_applies_across_hosts() {
+        let limiter = Limiter::new(&Limits {
+            downloads: 3,
+            ..Limits::default()
+        });
+        let urls = ["https://a.example.com/1.mp3", "https://b.example.com/2.mp3"];
+        assert_eq!(
+            peak(&limiter, &[urls[0], urls[1], urls[0], urls[1], urls[0]]).await,
+            3
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, IsTerminal},
+    path::Path,
+    sync::Mutex,
+};
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{
+    EnvFilter, Layer,
+    filter::{FilterExt, filter_fn},
+    fmt,
+    layer::Filter,
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+};
+
+use crate::progress::Progress;
+
+/// Environment variable read for a filter when `--log-filter` is not given.
+pub const FILTER_ENV: &str = "RUST_LOG";
+
+/// Sends log events to `console` and, with `file`, to that file as one
+/// JSON object per line. `filter` is in `RUST_LOG` syntax; without it
+/// (or `RUST_LOG`), `verbosity` picks this crate's level: 0 for info,
+/// negative for less, positive for more.
+pub fn init(
+    verbosity: i8,
+    filter: Option<&str>,
+    file: Option<&Path>,
+    console: Progress,
+) -> io::Result<()> {
+    let directives = match filter
+        .map(str::to_string)
+        .or_else(|| env::var(FILTER_ENV).ok())
+    {
+        Some(directives) => directives,
+        None => {
+            let level = level(verbosity);
+            format!(
+                "{},{}={}",
+                level.min(LevelFilter::WARN),
+                env!("CARGO_CRATE_NAME"),
+                level
+            )
+        }
+    };
+    let console = fmt::layer()
+        .with_writer(console)
+        .with_ansi(io::stderr().is_terminal())
+        .without_time()
+        .with_target(false)
+        .with_filter(crate_filter(&directives)?);
+
+    let file = match file {
+        Some(path) => {
+            let log = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+            Some(
+                fmt::layer()
+                    .json()
+                    .with_span_list(true)
+                    .with_writer(Mutex::new(log))
+                    .with_filter(crate_filter(&directives)?),
+            )
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(console)
+        .with(file)
+        .try_init()
+        .map_err(io::Error::other)
+}
+
+/// `directives`, with this crate's spans enabled whatever the level so
+/// that the warnings and errors left under `-q` still name their series
+/// and episode.
+fn crate_filter<S>(directives: &str) -> io::Result<impl Filter<S> + use<S>> {
+    let filter = EnvFilter::try_new(directives).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid log filter {:?}: {}", directives, e),
+        )
+    })?;
+    Ok(filter.or(filter_fn(|metadata| {
+        metadata.is_span() && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    })))
+}
+
+fn level(verbosity: i8) -> LevelFilter {
+    match verbosity {
+        ..=-2 => LevelFilter::ERROR,
+        -1 => LevelFilter::WARN,
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        2.. => LevelFilter::TRACE,
+    }
+}
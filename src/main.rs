@@ -1,234 +1,167 @@
-use std::{
-    fs::{self, File},
-    io::{self, Write},
-    path::{Path, PathBuf},
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
-    },
-};
-
-use chrono::Local;
-use futures::future::join_all;
-use scraper::{Html, Selector};
-
-const _6MINUTES_ENGLISH: &str = "https://www.bbc.co.uk/programmes/p02pc9tn/episodes/downloads";
-const _6MINUTES_VOCABULARY: &str = "https://www.bbc.co.uk/programmes/p02pc9xz/episodes/downloads";
-const _6MINUTES_GRAMMAR: &str = "https://www.bbc.co.uk/programmes/p02pc9wq/episodes/downloads";
-
-#[derive(Debug, Clone)]
-pub struct PodcastConfig {
-    pub name: String,
-    pub url: String,
-    pub download_folder: PathBuf,
-}
-
-pub struct PodcastDownloader {
-    config: PodcastConfig,
-    index_file: PathBuf,
-}
-
-impl PodcastDownloader {
-    fn new(name: &str, url: &str, folder: &str) -> io::Result<Self> {
-        let download_folder = Path::new(folder).to_path_buf();
-        fs::create_dir_all(&download_folder)?;
-
-        let index_file = download_folder.join(".podcast_index");
-
-        if !index_file.exists() {
-            let mut file = File::create(&index_file)?;
-            writeln!(file, "Generate Podcast Downloader\n{}", "-".repeat(40))?;
-        }
-        Ok(Self {
-            config: PodcastConfig {
-                name: name.to_string(),
-                url: url.to_string(),
-                download_folder,
-            },
-            index_file,
-        })
-    }
-
-    pub async fn download_episodes(&self) -> io::Result<()> {
-        println!("Checking for new {} episodes...", self.config.name);
-
-        let response = reqwest::get(&self.config.url)
-            .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-
-        let html = response
-            .text()
-            .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-
-        let document = Html::parse_document(&html);
-        let selector = Selector::parse("a[href$=\".mp3\"]")
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Invalid selector"))?;
-
-        // Collect all download links first
-        let mut download_tasks = Vec::new();
-
-        for element in document.select(&selector) {
-            if let Some(href) = element.value().attr("href") {
-                if !href.contains("audio-nondrm-download-low")
-                    && !self.is_already_downloaded(href)?
-                {
-                    if let Ok(filename) = self.extract_filename(element) {
-                        download_tasks.push((href.to_string(), filename));
-                    }
+mod anki;
+mod artwork;
+mod cli;
+mod commands;
+mod config;
+mod downloader;
+mod episode;
+mod feed;
+mod fetch;
+mod index;
+mod limiter;
+mod logging;
+mod markup;
+mod mime;
+mod naming;
+mod progress;
+mod report;
+mod retry;
+mod schedule;
+mod selectors;
+mod server;
+mod source;
+mod tags;
+mod transcript;
+mod vocabulary;
+mod watch;
+
+use std::{io, process::ExitCode};
+
+use clap::Parser;
+use tracing::error;
+
+use cli::{Cli, Command, Export};
+use commands::Outcome;
+use config::Config;
+use downloader::SyncOptions;
+use progress::Progress;
+
+async fn run(cli: Cli, progress: Progress) -> io::Result<Outcome> {
+    let config = Config::load(cli.config.as_deref())?;
+
+    match cli.command {
+        Command::Sync {
+            series,
+            pages,
+            full_archive,
+            retry_failed,
+            report,
+            report_file,
+        } => {
+            let limits = config.limits.clone();
+            let mut podcasts = commands::select_series(config, &series)?;
+            let pages = pages.or(full_archive.then_some(0));
+            if let Some(pages) = pages {
+                for podcast in &mut podcasts {
+                    podcast.max_pages = pages;
                 }
             }
+            let options = SyncOptions {
+                retry_failed,
+                walk_all: pages.is_some(),
+            };
+            commands::sync(
+                podcasts,
+                &limits,
+                options,
+                progress,
+                report,
+                report_file.as_deref(),
+            )
+            .await
         }
-
-        let total = download_tasks.len();
-        if total == 0 {
-            println!("No new {} episodes found", self.config.name);
-            return Ok(());
-        }
-
-        println!("Found {} new episodes, downloading...", total);
-
-        // Use futures::future::join_all for concurrent downloads without spawning
-        let semaphore = Arc::new(tokio::sync::Semaphore::new(4));
-        let completed = Arc::new(AtomicUsize::new(0));
-        let failed = Arc::new(AtomicUsize::new(0));
-
-        let download_futures = download_tasks
-            .into_iter()
-            .map(|(url, filename)| {
-                let semaphore = Arc::clone(&semaphore);
-                let completed = Arc::clone(&completed);
-                let failed = Arc::clone(&failed);
-                let download_folder = self.config.download_folder.clone();
-                let index_file = self.index_file.clone();
-
-                async move {
-                    let _permit = semaphore.acquire().await.unwrap();
-
-                    let filepath = download_folder.join(&filename);
-
-                    match Self::download_file(&url, &filepath).await {
-                        Ok(_) => {
-                            if let Err(e) = Self::record_download(&index_file, &url) {
-                                eprintln!("Failed to record download: {}", e);
-                            }
-                            let comp_count = completed.fetch_add(1, Ordering::SeqCst) + 1;
-                            println!("Downloaded {}/{}: {}", comp_count, total, filename);
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to download {}: {}", filename, e);
-                            failed.fetch_add(1, Ordering::SeqCst);
-                        }
-                    }
+        Command::Watch {
+            series,
+            interval,
+            schedule,
+            retry_failed,
+        } => {
+            let limits = config.limits.clone();
+            let mut podcasts = commands::select_series(config, &series)?;
+            if let Some(schedule) = interval.or(schedule) {
+                for podcast in &mut podcasts {
+                    podcast.schedule = schedule.clone();
                 }
-            })
-            .collect::<Vec<_>>();
-
-        // Wait for all downloads to complete
-        join_all(download_futures).await;
-
-        let completed = completed.load(Ordering::SeqCst);
-        let failed = failed.load(Ordering::SeqCst);
-
-        println!(
-            "Download: {} completed, {} failed",
-            completed, failed
-        );
-
-        Ok(())
-    }
-
-    fn is_already_downloaded(&self, url: &str) -> io::Result<bool> {
-        match fs::read_to_string(&self.index_file) {
-            Ok(content) => Ok(content.contains(url)),
-            Err(_) => Ok(false),
+            }
+            commands::watch(podcasts, &limits, retry_failed, progress).await
         }
-    }
-
-    fn extract_filename(&self, element: scraper::ElementRef) -> io::Result<String> {
-        if let Some(download_attr) = element.value().attr("download") {
-            let clean_name = download_attr.replace(" ", "_");
-            Ok(clean_name
-                .split(",")
-                .nth(1)
-                .unwrap_or(&clean_name)
-                .to_string())
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                "No download attribute found",
-            ))
+        Command::List { series } => commands::list(commands::select_series(config, &series)?).await,
+        Command::Status => commands::status(config.podcasts),
+        Command::Verify => commands::verify(config.podcasts),
+        Command::Retag { series } => {
+            commands::retag(commands::select_series(config, &series)?).await
         }
-    }
-
-    async fn download_file(url: &str, path: &Path) -> io::Result<()> {
-        let response = reqwest::get(format!("https:{}", url))
-            .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-
-        let bytes = response
-            .bytes()
+        Command::Transcripts { series, force } => {
+            commands::transcripts(commands::select_series(config, &series)?, force).await
+        }
+        Command::Export { what } => match what {
+            Export::Vocabulary {
+                series,
+                format,
+                output,
+            } => commands::export_vocabulary(
+                commands::select_series(config, &series)?,
+                format,
+                output.as_deref(),
+            ),
+            Export::Anki {
+                series,
+                output,
+                deck,
+                audio,
+            } => commands::export_anki(
+                commands::select_series(config, &series)?,
+                output.as_deref(),
+                &deck,
+                audio,
+            ),
+        },
+        Command::Feed { series, base_url } => commands::feed(
+            commands::select_series(config, &series)?,
+            base_url.as_deref(),
+        ),
+        Command::Serve {
+            series,
+            listen,
+            base_url,
+        } => commands::serve(commands::select_series(config, &series)?, listen, base_url).await,
+        Command::Probe { series, file, page } => {
+            commands::probe(
+                commands::select_series(config, &series)?,
+                file.as_deref(),
+                page.max(1),
+            )
             .await
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-
-        tokio::fs::write(path, bytes).await?;
-        Ok(())
-    }
-
-    fn record_download(index_file: &Path, url: &str) -> io::Result<()> {
-        let mut file = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(index_file)?;
-        writeln!(file, "{} {}", Local::now().format("%Y%m%d%H%M%S"), url)?;
-        Ok(())
+        }
+        Command::Rename { series, apply } => {
+            commands::rename(commands::select_series(config, &series)?, apply)
+        }
     }
 }
 
 #[tokio::main]
-async fn main() -> io::Result<()> {
-    println!("BBC Scraper\n");
-
-    let podcasts = vec![
-        PodcastConfig {
-            name: "6MinuteEnglish".to_string(),
-            url: "https://www.bbc.co.uk/programmes/p02pc9tn/episodes/downloads".to_string(),
-            download_folder: PathBuf::from("./podcasts/6min_english"),
-        },
-        PodcastConfig {
-            name: "6 Minute Vocabulary".to_string(),
-            url: "https://www.bbc.co.uk/programmes/p02pc9xz/episodes/downloads".to_string(),
-            download_folder: PathBuf::from("./podcasts/6min_vocabulary"),
-        },
-        PodcastConfig {
-            name: "6 Minute Grammar".to_string(),
-            url: "https://www.bbc.co.uk/programmes/p02pc9wq/episodes/downloads".to_string(),
-            download_folder: PathBuf::from("./podcasts/6min_grammar"),
-        },
-    ];
-
-    // Process podcasts sequentially to avoid Send issues
-    for config in podcasts {
-        match PodcastDownloader::new(
-            &config.name,
-            &config.url,
-            config.download_folder.to_str().unwrap(),
-        ) {
-            Ok(downloader) => {
-                println!("Starting downloader for {}...", config.name);
-
-                if let Err(e) = downloader.download_episodes().await {
-                    eprintln!("Error downloading {}: {}", config.name, e);
-                }
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    // A long-running watch only logs; its finished bars would pile up.
+    let bars = cli.quiet == 0 && !matches!(cli.command, Command::Watch { .. });
+    let progress = Progress::for_stderr(bars);
+    let verbosity = (cli.verbose as i8).saturating_sub(cli.quiet as i8);
+    if let Err(e) = logging::init(
+        verbosity,
+        cli.log_filter.as_deref(),
+        cli.log_file.as_deref(),
+        progress.clone(),
+    ) {
+        eprintln!("Error: {}", e);
+        return ExitCode::FAILURE;
+    }
 
-                println!("Finished {}", config.name);
-            }
-            Err(e) => {
-                eprintln!("Failed to initialize {} downloader: {}", config.name, e);
-            }
+    match run(cli, progress).await {
+        Ok(outcome) => outcome.into(),
+        Err(e) => {
+            error!("{}", e);
+            ExitCode::FAILURE
         }
     }
-
-    println!("All podcast downloads completed!");
-    Ok(())
 }
@@ -0,0 +1,220 @@
+use chrono::NaiveDate;
+
+pub const DEFAULT_TEMPLATE: &str = "{title}_-_{pid}.{ext}";
+
+const PLACEHOLDERS: &[&str] = &["series", "date", "title", "pid", "vpid", "ext"];
+
+/// What `{date}` becomes for an episode without a broadcast date.
+const UNDATED: &str = "undated";
+
+/// Longest filename we produce, in bytes, leaving room for a collision
+/// suffix and `.part.meta` within the usual 255-byte limit.
+const MAX_NAME_LEN: usize = 200;
+
+/// Names Windows refuses as the stem of a file.
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The values a filename template can refer to.
+#[derive(Debug, Clone)]
+pub struct NameFields<'a> {
+    pub series: &'a str,
+    /// The broadcast date, when known.
+    pub date: Option<NaiveDate>,
+    pub title: &'a str,
+    pub pid: Option<&'a str>,
+    pub vpid: &'a str,
+    pub ext: &'a str,
+}
+
+/// Checks that `template` only uses known placeholders and stays inside
+/// the download folder.
+pub fn validate(template: &str) -> Result<(), String> {
+    if template.contains('/') || template.contains('\\') {
+        return Err(format!("'{}' must not contain path separators", template));
+    }
+
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!("unclosed '{{' in '{}'", template));
+        };
+        let name = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder {{{}}} in '{}' (known: {})",
+                name,
+                template,
+                PLACEHOLDERS
+                    .iter()
+                    .map(|p| format!("{{{}}}", p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+/// Fills in `template`, sanitising every value so the result is a valid
+/// filename on Linux, macOS and Windows.
+pub fn render(template: &str, fields: &NameFields) -> String {
+    let date = fields.date.map_or_else(
+        || UNDATED.to_string(),
+        |date| date.format("%Y-%m-%d").to_string(),
+    );
+    let name = template
+        .replace("{series}", &sanitize(fields.series))
+        .replace("{date}", &date)
+        .replace("{title}", &sanitize(fields.title))
+        .replace("{pid}", &sanitize(fields.pid.unwrap_or(fields.vpid)))
+        .replace("{vpid}", &sanitize(fields.vpid))
+        .replace("{ext}", &sanitize(fields.ext));
+    finish(&name)
+}
+
+/// Makes a template value safe to put in a filename: spaces become `_`,
+/// quotes and `?` are dropped, other reserved characters become `-`.
+pub fn sanitize(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.trim().chars() {
+        match c {
+            '?' | '\'' | '"' | '‘' | '’' | '“' | '”' => {}
+            ':' | '/' | '\\' | '|' | '*' | '<' | '>' => out.push('-'),
+            c if c.is_whitespace() => out.push('_'),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Trims what Windows rejects at the end of a name, shortens it to
+/// `MAX_NAME_LEN` bytes and avoids reserved device names.
+fn finish(name: &str) -> String {
+    let (stem, ext) = split_ext(name);
+
+    let mut stem = stem.trim_end_matches(['.', ' ']).to_string();
+    let budget = MAX_NAME_LEN.saturating_sub(ext.len());
+    if stem.len() > budget {
+        let mut cut = budget;
+        while !stem.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        stem.truncate(cut);
+    }
+    if stem.is_empty() || RESERVED.iter().any(|r| r.eq_ignore_ascii_case(&stem)) {
+        stem.insert(0, '_');
+    }
+
+    format!("{}{}", stem, ext)
+}
+
+/// Splits `name` into stem and extension, the extension keeping its dot.
+fn split_ext(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(dot) => name.split_at(dot),
+        None => (name, ""),
+    }
+}
+
+/// `name`, or `name` with a `_2`, `_3`... suffix before the extension if
+/// `taken` says it is in use.
+pub fn unique(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_string();
+    }
+
+    let (stem, ext) = split_ext(name);
+    (2..)
+        .map(|n| format!("{}_{}{}", stem, n, ext))
+        .find(|candidate| !taken(candidate))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields<'a>(title: &'a str, pid: Option<&'a str>) -> NameFields<'a> {
+        NameFields {
+            series: "6 Minute Grammar",
+            date: NaiveDate::from_ymd_opt(2024, 3, 7),
+            title,
+            pid,
+            vpid: "p0c4x2m0",
+            ext: "mp3",
+        }
+    }
+
+    #[test]
+    fn renders_default_template() {
+        assert_eq!(
+            render(
+                DEFAULT_TEMPLATE,
+                &fields("'Can', 'could' and 'be able to'", Some("p0c4x2lz"))
+            ),
+            "Can,_could_and_be_able_to_-_p0c4x2lz.mp3"
+        );
+    }
+
+    #[test]
+    fn renders_every_placeholder() {
+        assert_eq!(
+            render(
+                "{series}_{date}_{title}_{pid}_{vpid}.{ext}",
+                &fields("Modals", Some("p0c4x2lz"))
+            ),
+            "6_Minute_Grammar_2024-03-07_Modals_p0c4x2lz_p0c4x2m0.mp3"
+        );
+    }
+
+    #[test]
+    fn date_without_broadcast_date() {
+        let fields = NameFields {
+            date: None,
+            ..fields("Modals", Some("p0c4x2lz"))
+        };
+        assert_eq!(
+            render("{date}_{title}.{ext}", &fields),
+            "undated_Modals.mp3"
+        );
+    }
+
+    #[test]
+    fn pid_falls_back_to_vpid() {
+        assert_eq!(
+            render(DEFAULT_TEMPLATE, &fields("Modals", None)),
+            "Modals_-_p0c4x2m0.mp3"
+        );
+    }
+
+    #[test]
+    fn render_avoids_reserved_and_empty_names() {
+        assert_eq!(render("{title}.{ext}", &fields("con", None)), "_con.mp3");
+        assert_eq!(render("{title}.{ext}", &fields("???", None)), "_.mp3");
+        assert_eq!(render("{title}.{ext}", &fields("Why. ", None)), "Why.mp3");
+    }
+
+    #[test]
+    fn render_shortens_long_names_on_char_boundaries() {
+        let title = "é".repeat(150);
+        let name = render(DEFAULT_TEMPLATE, &fields(&title, Some("p0c4x2lz")));
+        assert!(name.len() <= MAX_NAME_LEN);
+        assert!(name.ends_with(".mp3"));
+    }
+
+    #[test]
+    fn sanitize_replaces_reserved_characters() {
+        assert_eq!(
+            sanitize(" What's \"new\"? Part 1: A/B "),
+            "Whats_new_Part_1-_A-B"
+        );
+        assert_eq!(sanitize("‘Quoted’ “text”"), "Quoted_text");
+        assert_eq!(sanitize("a|b*c<d>e\\f"), "a-b-c-d-e-f");
+        assert_eq!(sanitize("tab\there\u{7}"), "tab_here");
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    time::Duration,
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Width of the file name column of download bars.
+const NAME_WIDTH: usize = 24;
+
+/// What a sync shows on stderr while it runs: on a terminal, a bar per
+/// series and one per active download, with log lines printed above them;
+/// otherwise only the log lines.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    bars: Option<MultiProgress>,
+}
+
+impl Progress {
+    /// Bars when `bars` is set and stderr is a terminal.
+    pub fn for_stderr(bars: bool) -> Self {
+        Self {
+            bars: (bars && io::stderr().is_terminal())
+                .then(|| MultiProgress::with_draw_target(ProgressDrawTarget::stderr())),
+        }
+    }
+
+    /// The overall bar of a series about to download `total` episodes.
+    pub fn series(&self, name: &str, total: usize) -> SeriesProgress {
+        let Some(bars) = &self.bars else {
+            return SeriesProgress::default();
+        };
+        let bar = bars.add(ProgressBar::new(total as u64));
+        bar.set_style(
+            ProgressStyle::with_template("{prefix:.bold} [{bar:30}] {pos}/{len} episodes {msg}")
+                .expect("valid template")
+                .progress_chars("=> "),
+        );
+        bar.set_prefix(name.to_string());
+        SeriesProgress {
+            bars: Some((bars.clone(), bar)),
+        }
+    }
+}
+
+/// Log lines go to stderr through the bars, so they never tear them.
+impl<'a> MakeWriter<'a> for Progress {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogWriter {
+            bars: self.bars.clone(),
+            line: Vec::new(),
+        }
+    }
+}
+
+/// Collects one log event and prints it when dropped.
+pub struct LogWriter {
+    bars: Option<MultiProgress>,
+    line: Vec<u8>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.line.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        let print = || {
+            let _ = io::stderr().write_all(&self.line);
+        };
+        match &self.bars {
+            Some(bars) => bars.suspend(print),
+            None => print(),
+        }
+    }
+}
+
+/// The overall bar of one series, and the parent of its download bars.
+#[derive(Debug, Default)]
+pub struct SeriesProgress {
+    bars: Option<(MultiProgress, ProgressBar)>,
+}
+
+impl SeriesProgress {
+    /// A bar for the download of `filename`, shown below the series bar
+    /// until it is finished.
+    pub fn download(&self, filename: &str) -> DownloadProgress {
+        let Some((bars, series)) = &self.bars else {
+            return DownloadProgress::default();
+        };
+        let bar = bars.insert_after(series, ProgressBar::no_length());
+        bar.set_style(unknown_length_style());
+        bar.set_message(fit(filename, NAME_WIDTH));
+        bar.enable_steady_tick(Duration::from_millis(200));
+        DownloadProgress { bar: Some(bar) }
+    }
+
+    /// Counts one episode as done, downloaded or failed.
+    pub fn inc(&self) {
+        if let Some((_, bar)) = &self.bars {
+            bar.inc(1);
+        }
+    }
+
+    /// Leaves the series bar on screen with `summary` after it.
+    pub fn finish(&self, summary: &str) {
+        if let Some((_, bar)) = &self.bars {
+            bar.finish_with_message(summary.to_string());
+        }
+    }
+}
+
+/// The bar of one download: bytes, speed and time left.
+#[derive(Debug, Default)]
+pub struct DownloadProgress {
+    bar: Option<ProgressBar>,
+}
+
+impl DownloadProgress {
+    /// Shows `bytes` received of `total`, when the server said how many.
+    pub fn update(&self, bytes: u64, total: Option<u64>) {
+        let Some(bar) = &self.bar else {
+            return;
+        };
+        if let Some(total) = total
+            && bar.length() != Some(total)
+        {
+            bar.set_length(total);
+            bar.set_style(known_length_style());
+        }
+        bar.set_position(bytes);
+    }
+}
+
+impl Drop for DownloadProgress {
+    /// Removes the bar once the download is over, however it ended.
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+fn known_length_style() -> ProgressStyle {
+    ProgressStyle::with_template(
+        "  {msg} [{bar:12}] {bytes:>8}/{total_bytes:<8} {binary_bytes_per_sec:>10} ETA {eta:>3}",
+    )
+    .expect("valid template")
+    .progress_chars("=> ")
+}
+
+fn unknown_length_style() -> ProgressStyle {
+    ProgressStyle::with_template("  {msg} {spinner} {bytes:>8} {binary_bytes_per_sec:>10}")
+        .expect("valid template")
+}
+
+/// `name` cut or padded to `width` characters, so the bars line up.
+fn fit(name: &str, width: usize) -> String {
+    let count = name.chars().count();
+    if count <= width {
+        format!("{:<width$}", name, width = width)
+    } else {
+        let head = name.chars().take(width - 3).collect::<String>();
+        format!("{}...", head)
+    }
+}
@@ -0,0 +1,51 @@
+use std::io::{self, Write};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::{commands::Outcome, downloader::SyncSummary};
+
+/// What a `sync` run did, for tools that act on new episodes.
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    /// How the run ended; the exit code follows from it.
+    pub status: Outcome,
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    /// In config order.
+    pub series: Vec<SeriesReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeriesReport {
+    pub name: String,
+    /// Why the series could not be synced, when it stopped before its
+    /// downloads.
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub summary: SyncSummary,
+}
+
+impl SeriesReport {
+    pub fn new(name: &str, summary: SyncSummary) -> Self {
+        Self {
+            name: name.to_string(),
+            error: None,
+            summary,
+        }
+    }
+
+    /// A series that failed with `error` before it got to download.
+    pub fn failed(name: &str, error: &io::Error) -> Self {
+        Self {
+            name: name.to_string(),
+            error: Some(error.to_string()),
+            summary: SyncSummary::default(),
+        }
+    }
+}
+
+pub fn write_json(out: &mut impl Write, report: &RunReport) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, report)?;
+    writeln!(out)
+}
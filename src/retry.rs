@@ -0,0 +1,174 @@
+use std::{io, time::Duration};
+
+use reqwest::StatusCode;
+use serde::Serialize;
+use tracing::warn;
+
+/// Whether a failed download is worth trying again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorClass {
+    /// Timeouts, dropped connections and server errors.
+    Transient,
+    /// Missing or forbidden files and unexpected content; retrying will
+    /// give the same answer.
+    Permanent,
+}
+
+impl ErrorClass {
+    pub fn of(e: &io::Error) -> Self {
+        if let Some(e) = e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<reqwest::Error>())
+        {
+            return match e.status() {
+                Some(status) => Self::of_status(status),
+                None if e.is_builder() || e.is_redirect() => Self::Permanent,
+                None => Self::Transient,
+            };
+        }
+
+        match e.kind() {
+            io::ErrorKind::InvalidData => Self::Permanent,
+            _ => Self::Transient,
+        }
+    }
+
+    fn of_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS => Self::Transient,
+            status if status.is_client_error() => Self::Permanent,
+            _ => Self::Transient,
+        }
+    }
+}
+
+/// How often and how patiently to retry transient failures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one.
+    pub attempts: u32,
+    /// Delay before the first retry; doubled for every further retry.
+    pub backoff: Duration,
+    /// Fraction of each delay, between 0 and 1, that is randomised.
+    pub jitter: f64,
+}
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Duration::from_secs(1),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay after failed attempt number `attempt` (starting at 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(MAX_BACKOFF);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        exponential.mul_f64(1.0 - jitter * rand::random::<f64>())
+    }
+
+    /// Runs `op` until it succeeds, fails permanently or runs out of
+    /// attempts. `what` names the operation in retry messages.
+    pub async fn run<T, F, Fut>(&self, what: &str, mut op: F) -> io::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = io::Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Ok(value) => return Ok(value),
+                Err(e)
+                    if attempt < self.attempts && ErrorClass::of(&e) == ErrorClass::Transient =>
+                {
+                    let delay = self.delay(attempt);
+                    warn!(
+                        "{} failed ({}), retrying in {:.1}s ({}/{})",
+                        what,
+                        e,
+                        delay.as_secs_f64(),
+                        attempt,
+                        self.attempts - 1
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_classes() {
+        for (status, class) in [
+            (StatusCode::BAD_REQUEST, ErrorClass::Permanent),
+            (StatusCode::FORBIDDEN, ErrorClass::Permanent),
+            (StatusCode::NOT_FOUND, ErrorClass::Permanent),
+            (StatusCode::GONE, ErrorClass::Permanent),
+            (StatusCode::REQUEST_TIMEOUT, ErrorClass::Transient),
+            (StatusCode::TOO_MANY_REQUESTS, ErrorClass::Transient),
+            (StatusCode::INTERNAL_SERVER_ERROR, ErrorClass::Transient),
+            (StatusCode::BAD_GATEWAY, ErrorClass::Transient),
+            (StatusCode::SERVICE_UNAVAILABLE, ErrorClass::Transient),
+        ] {
+            assert_eq!(ErrorClass::of_status(status), class, "{}", status);
+        }
+    }
+
+    #[test]
+    fn io_error_classes() {
+        assert_eq!(
+            ErrorClass::of(&io::Error::new(io::ErrorKind::InvalidData, "text/html")),
+            ErrorClass::Permanent
+        );
+        assert_eq!(
+            ErrorClass::of(&io::Error::from(io::ErrorKind::UnexpectedEof)),
+            ErrorClass::Transient
+        );
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            attempts: 10,
+            backoff: Duration::from_secs(1),
+            jitter: 0.0,
+        };
+        for (attempt, seconds) in [(1, 1), (2, 2), (3, 4), (4, 8), (6, 32), (7, 60), (40, 60)] {
+            assert_eq!(
+                policy.delay(attempt),
+                Duration::from_secs(seconds),
+                "attempt {}",
+                attempt
+            );
+        }
+    }
+
+    #[test]
+    fn jitter_only_shortens_the_delay() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_secs(4),
+            jitter: 0.5,
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(2);
+            assert!(delay > Duration::from_secs(4) && delay <= Duration::from_secs(8));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::{fmt, time::Duration};
+
+use chrono::{DateTime, Local, TimeDelta};
+use croner::Cron;
+
+/// Time between two checks of a series in `watch` mode when neither
+/// `interval` nor `schedule` is set.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// When `watch` checks a series.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// Every so often, counted from the start of the previous check.
+    Every(Duration),
+    /// Whenever a cron expression matches, in local time.
+    Cron(Box<Cron>),
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::Every(DEFAULT_INTERVAL)
+    }
+}
+
+impl Schedule {
+    /// Parses an interval such as `30m`, `6h` or `1h 30m`.
+    pub fn interval(text: &str) -> Result<Self, String> {
+        let interval = humantime::parse_duration(text)
+            .map_err(|e| format!("invalid interval {:?}: {}", text, e))?;
+        if interval.is_zero() {
+            return Err("interval must be longer than zero".to_string());
+        }
+        Ok(Self::Every(interval))
+    }
+
+    /// Parses a cron expression of minute, hour, day of month, month and
+    /// day of week, e.g. `0 7 * * 1-5` for 7:00 on weekdays.
+    pub fn cron(text: &str) -> Result<Self, String> {
+        let cron = text
+            .parse::<Cron>()
+            .map_err(|e| format!("invalid schedule {:?}: {}", text, e))?;
+        if cron.find_next_occurrence(&Local::now(), false).is_err() {
+            return Err(format!("schedule {:?} never matches", text));
+        }
+        Ok(Self::Cron(Box::new(cron)))
+    }
+
+    /// When to check next, now that a check started at `started` is over.
+    /// Checks whose time passed while it ran are skipped; `None` when the
+    /// schedule has no time left.
+    pub fn next(&self, started: DateTime<Local>, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Self::Every(interval) => {
+                let interval = TimeDelta::from_std(*interval).ok()?;
+                let mut next = started.checked_add_signed(interval)?;
+                while next <= now {
+                    next = next.checked_add_signed(interval)?;
+                }
+                Some(next)
+            }
+            Self::Cron(cron) => cron.find_next_occurrence(&now, false).ok(),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Every(interval) => write!(f, "every {}", humantime::format_duration(*interval)),
+            Self::Cron(cron) => write!(f, "at \"{}\"", cron),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, 7, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn interval_counts_from_the_start_of_the_check() {
+        let schedule = Schedule::interval("30m").unwrap();
+        assert_eq!(schedule.to_string(), "every 30m");
+        assert_eq!(
+            schedule.next(at(18, 8, 0), at(18, 8, 10)),
+            Some(at(18, 8, 30))
+        );
+        // A check that ran past its next time skips it.
+        assert_eq!(
+            schedule.next(at(18, 8, 0), at(18, 9, 5)),
+            Some(at(18, 9, 30))
+        );
+    }
+
+    #[test]
+    fn cron_picks_the_next_match() {
+        let schedule = Schedule::cron("0 7 * * 1-5").unwrap();
+        // Friday after 7:00, so the next weekday morning is Monday's.
+        assert_eq!(
+            schedule.next(at(18, 7, 0), at(18, 8, 0)),
+            Some(at(21, 7, 0))
+        );
+        assert_eq!(
+            schedule.next(at(21, 6, 0), at(21, 6, 30)),
+            Some(at(21, 7, 0))
+        );
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        let e = Schedule::cron("every morning").unwrap_err();
+        assert!(e.starts_with("invalid schedule \"every morning\""), "{}", e);
+        let e = Schedule::interval("soon").unwrap_err();
+        assert!(e.starts_with("invalid interval \"soon\""), "{}", e);
+        assert_eq!(
+            Schedule::interval("0s").unwrap_err(),
+            "interval must be longer than zero"
+        );
+        assert_eq!(
+            Schedule::cron("0 0 30 2 *").unwrap_err(),
+            "schedule \"0 0 30 2 *\" never matches"
+        );
+    }
+}
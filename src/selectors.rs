@@ -0,0 +1,136 @@
+use scraper::Selector;
+
+/// Placeholder in `next_page` for the number of the page after the
+/// current one.
+pub const PAGE_PLACEHOLDER: &str = "{page}";
+
+/// Where the parts of an episode are found on an HTML listing page. Card
+/// selectors match inside the episode's card: the outermost element
+/// around its audio link that holds no other episode's link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selectors {
+    /// The audio link of each episode.
+    pub episode: String,
+    /// Attribute of the audio link holding its URL.
+    pub url_attribute: String,
+    /// Attribute of the audio link holding the suggested file name, which
+    /// BBC fills with the title and programme id.
+    pub download_attribute: String,
+    pub title: String,
+    pub synopsis: String,
+    /// Read from a `datetime` or `content` attribute, else from the text.
+    pub date: String,
+    /// Read from a `content` or `datetime` attribute, else from the text.
+    pub duration: String,
+    /// Link to the episode page; the first one with a programme id counts.
+    pub page_link: String,
+    pub image: String,
+    /// Matches on a page that has a next page; `{page}` is its number.
+    pub next_page: String,
+}
+
+impl Default for Selectors {
+    fn default() -> Self {
+        Self {
+            episode: "a[href$=\".mp3\"]".to_string(),
+            url_attribute: "href".to_string(),
+            download_attribute: "download".to_string(),
+            title: ".programme__title".to_string(),
+            synopsis: ".programme__synopsis".to_string(),
+            date: "time[datetime], [datatype=\"xsd:date\"], [property=\"startDate\"], \
+                   [itemprop=\"datePublished\"], .broadcast-event__date"
+                .to_string(),
+            duration: "[property=\"duration\"], [itemprop=\"duration\"], \
+                       .programme__duration, .duration"
+                .to_string(),
+            page_link: "a[href*=\"/programmes/\"]".to_string(),
+            image: "img".to_string(),
+            next_page: "a[href*=\"page={page}\"]".to_string(),
+        }
+    }
+}
+
+impl Selectors {
+    /// The CSS selectors by their config key, in page order.
+    pub fn css(&self) -> [(&'static str, &str); 8] {
+        [
+            ("episode", &self.episode),
+            ("title", &self.title),
+            ("synopsis", &self.synopsis),
+            ("date", &self.date),
+            ("duration", &self.duration),
+            ("page_link", &self.page_link),
+            ("image", &self.image),
+            ("next_page", &self.next_page),
+        ]
+    }
+
+    /// The `next_page` selector for a page numbered `page`.
+    pub fn next_page(&self, page: usize) -> String {
+        self.next_page.replace(PAGE_PLACEHOLDER, &page.to_string())
+    }
+
+    /// Checks that every selector parses and no attribute name is empty.
+    pub fn validate(&self) -> Result<(), String> {
+        for (key, css) in self.css() {
+            let css = if key == "next_page" {
+                self.next_page(2)
+            } else {
+                css.to_string()
+            };
+            Selector::parse(&css).map_err(|_| format!("{}: invalid selector {:?}", key, css))?;
+        }
+        for (key, attribute) in [
+            ("url_attribute", &self.url_attribute),
+            ("download_attribute", &self.download_attribute),
+        ] {
+            if attribute.trim().is_empty() {
+                return Err(format!("{} must not be empty", key));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_selectors_are_valid() {
+        assert_eq!(Selectors::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_selector_is_named() {
+        let selectors = Selectors {
+            synopsis: "p[class=".to_string(),
+            ..Selectors::default()
+        };
+        assert_eq!(
+            selectors.validate(),
+            Err("synopsis: invalid selector \"p[class=\"".to_string())
+        );
+
+        let selectors = Selectors {
+            next_page: "a[href*={page}]".to_string(),
+            ..Selectors::default()
+        };
+        assert_eq!(
+            selectors.validate(),
+            Err("next_page: invalid selector \"a[href*=2]\"".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_attribute_is_named() {
+        let selectors = Selectors {
+            url_attribute: " ".to_string(),
+            ..Selectors::default()
+        };
+        assert_eq!(
+            selectors.validate(),
+            Err("url_attribute must not be empty".to_string())
+        );
+    }
+}
@@ -0,0 +1,435 @@
+use std::{
+    fs,
+    io::{self, SeekFrom},
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path as UrlPath, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, HOST, RANGE},
+    },
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+use tracing::{error, info};
+
+use crate::{
+    config::PodcastConfig,
+    feed::{self, FEED_FILE_NAME},
+    index::{EpisodeRecord, Index},
+    markup::escape,
+    mime,
+};
+
+/// Cached images are served from here so feeds can use them as artwork.
+const ARTWORK_DIR: &str = ".artwork/";
+
+struct Server {
+    series: Vec<Series>,
+    /// Overrides every series' `base_url` in feeds.
+    base_url: Option<String>,
+}
+
+/// A served series and its index as last read.
+struct Series {
+    podcast: PodcastConfig,
+    index: RwLock<LoadedIndex>,
+}
+
+struct LoadedIndex {
+    /// Modification time and size of the file when it was read.
+    stamp: Option<(SystemTime, u64)>,
+    index: Arc<Index>,
+}
+
+impl Series {
+    fn open(podcast: PodcastConfig) -> io::Result<Self> {
+        let index = Index::open(&podcast.download_folder, &podcast.name)?;
+        Ok(Self {
+            index: RwLock::new(LoadedIndex {
+                stamp: stamp(index.path()),
+                index: Arc::new(index),
+            }),
+            podcast,
+        })
+    }
+
+    /// The index, read again when a sync changed the file since.
+    fn index(&self) -> io::Result<Arc<Index>> {
+        let loaded = self.index.read().unwrap();
+        let stamp = stamp(loaded.index.path());
+        if loaded.stamp == stamp {
+            return Ok(Arc::clone(&loaded.index));
+        }
+        drop(loaded);
+
+        let index = Arc::new(Index::open(
+            &self.podcast.download_folder,
+            &self.podcast.name,
+        )?);
+        *self.index.write().unwrap() = LoadedIndex {
+            stamp,
+            index: Arc::clone(&index),
+        };
+        Ok(index)
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl Server {
+    fn series(&self, slug: &str) -> Option<&Series> {
+        self.series
+            .iter()
+            .find(|series| series.podcast.slug() == slug)
+    }
+
+    /// The URL feeds link files under: `--base-url`, the series'
+    /// `base_url`, or the host the request was sent to.
+    fn base_url(&self, podcast: &PodcastConfig, headers: &HeaderMap) -> String {
+        self.base_url
+            .clone()
+            .or_else(|| podcast.base_url.clone())
+            .unwrap_or_else(|| {
+                let host = headers
+                    .get(HOST)
+                    .and_then(|host| host.to_str().ok())
+                    .unwrap_or("localhost");
+                format!("http://{}", host)
+            })
+    }
+}
+
+/// Serves the series pages, feeds, audio files and transcripts on
+/// `listen` until interrupted.
+pub async fn run(
+    podcasts: Vec<PodcastConfig>,
+    listen: SocketAddr,
+    base_url: Option<String>,
+) -> io::Result<()> {
+    let server = Arc::new(Server {
+        series: podcasts
+            .into_iter()
+            .map(Series::open)
+            .collect::<io::Result<_>>()?,
+        base_url: base_url.map(|url| url.trim_end_matches('/').to_string()),
+    });
+    let app = Router::new()
+        .route("/", get(home))
+        .route("/{series}", get(series_redirect))
+        .route("/{series}/", get(series_page))
+        .route("/{series}/{*path}", get(series_file))
+        .with_state(Arc::clone(&server));
+
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!("Serving on http://{}", listener.local_addr()?);
+    for Series { podcast, .. } in &server.series {
+        info!(series = %podcast.name, "Serving /{}/", podcast.slug());
+    }
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}
+
+async fn home(State(server): State<Arc<Server>>) -> Html<String> {
+    let mut html = page_head("Podcasts");
+    html.push_str("<h1>Podcasts</h1>\n<ul>\n");
+    for Series { podcast, .. } in &server.series {
+        html.push_str(&format!(
+            "<li><a href=\"{}/\">{}</a> (<a href=\"{}/{}\">feed</a>)</li>\n",
+            podcast.slug(),
+            escape(&podcast.name),
+            podcast.slug(),
+            FEED_FILE_NAME
+        ));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    Html(html)
+}
+
+async fn series_redirect(UrlPath(slug): UrlPath<String>) -> Redirect {
+    Redirect::permanent(&format!("/{}/", feed::encode_path(&slug)))
+}
+
+/// A table of the downloaded episodes, newest first.
+async fn series_page(
+    State(server): State<Arc<Server>>,
+    UrlPath(slug): UrlPath<String>,
+) -> Response {
+    let Some(series) = server.series(&slug) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let podcast = &series.podcast;
+    let index = match series.index() {
+        Ok(index) => index,
+        Err(e) => return server_error(e),
+    };
+    let mut records = index
+        .episodes()
+        .filter(|record| record.filename.is_some())
+        .collect::<Vec<_>>();
+    records.sort_by_key(|record| std::cmp::Reverse(record.publish_order()));
+
+    let mut html = page_head(&podcast.name);
+    html.push_str(&format!(
+        "<h1>{}</h1>\n<p><a href=\"../\">All podcasts</a> · <a href=\"{}\">RSS feed</a> · {} episodes</p>\n",
+        escape(&podcast.name),
+        FEED_FILE_NAME,
+        records.len()
+    ));
+    html.push_str("<table>\n<tr><th>Date</th><th>Episode</th><th>Length</th><th>Audio</th><th>Transcript</th></tr>\n");
+    for record in records {
+        html.push_str(&episode_row(record));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    Html(html).into_response()
+}
+
+fn episode_row(record: &EpisodeRecord) -> String {
+    let title = escape(record.title.as_deref().unwrap_or(&record.vpid));
+    let title = match &record.page_url {
+        Some(url) => format!("<a href=\"{}\">{}</a>", escape(url), title),
+        None => title,
+    };
+    let synopsis = record
+        .synopsis
+        .as_deref()
+        .map(|synopsis| format!("<br><small>{}</small>", escape(synopsis)))
+        .unwrap_or_default();
+    let length = record
+        .duration
+        .map(|seconds| format!("{}:{:02}", seconds / 60, seconds % 60))
+        .unwrap_or_default();
+    let audio = record
+        .filename
+        .as_deref()
+        .map(|filename| {
+            format!(
+                "<audio controls preload=\"none\" src=\"{}\"></audio>",
+                feed::encode_path(filename)
+            )
+        })
+        .unwrap_or_default();
+    let mut transcript = Vec::new();
+    if let Some(path) = &record.transcript {
+        transcript.push(format!("<a href=\"{}\">text</a>", feed::encode_path(path)));
+    }
+    if let Some(path) = &record.transcript_pdf {
+        transcript.push(format!("<a href=\"{}\">PDF</a>", feed::encode_path(path)));
+    }
+
+    format!(
+        "<tr><td>{}</td><td>{}{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+        record.date(),
+        title,
+        synopsis,
+        length,
+        audio,
+        transcript.join(" ")
+    )
+}
+
+/// The feed, or a file the index refers to: an episode, a transcript or
+/// a cached image. Nothing else in the folder is reachable.
+async fn series_file(
+    State(server): State<Arc<Server>>,
+    UrlPath((slug, path)): UrlPath<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(series) = server.series(&slug) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let podcast = &series.podcast;
+    let index = match series.index() {
+        Ok(index) => index,
+        Err(e) => return server_error(e),
+    };
+
+    if path == FEED_FILE_NAME {
+        let series_url = format!("{}/{}", server.base_url(podcast, &headers), podcast.slug());
+        let (channel, items) = feed::for_series(podcast, &index, &series_url);
+        return (
+            [(CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+            feed::render(&channel, &items),
+        )
+            .into_response();
+    }
+
+    let known = index.downloaded().any(|record| {
+        [&record.filename, &record.transcript, &record.transcript_pdf]
+            .into_iter()
+            .any(|file| file.as_deref() == Some(path.as_str()))
+    });
+    let artwork = path
+        .strip_prefix(ARTWORK_DIR)
+        .is_some_and(|name| !name.is_empty() && !name.contains('/') && !name.starts_with('.'));
+    if !known && !artwork {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    send_file(
+        &podcast.download_folder.join(&path),
+        mime::of(&path),
+        &headers,
+    )
+    .await
+}
+
+/// Sends `path`, or the single byte range the request asks for.
+async fn send_file(path: &Path, content_type: &str, headers: &HeaderMap) -> Response {
+    let mut file = match File::open(path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        Err(e) => return server_error(e),
+    };
+    let len = match file.metadata().await {
+        Ok(metadata) => metadata.len(),
+        Err(e) => return server_error(e),
+    };
+
+    let range = headers
+        .get(RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_range(value, len));
+
+    let mut response = match range {
+        None => Response::new(Body::from_stream(ReaderStream::new(file))),
+        Some(None) => {
+            let mut response = StatusCode::RANGE_NOT_SATISFIABLE.into_response();
+            response
+                .headers_mut()
+                .insert(CONTENT_RANGE, header_value(&format!("bytes */{}", len)));
+            return response;
+        }
+        Some(Some((start, end))) => {
+            if let Err(e) = file.seek(SeekFrom::Start(start)).await {
+                return server_error(e);
+            }
+            let length = end - start + 1;
+            let mut response =
+                Response::new(Body::from_stream(ReaderStream::new(file.take(length))));
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            response.headers_mut().insert(
+                CONTENT_RANGE,
+                header_value(&format!("bytes {}-{}/{}", start, end, len)),
+            );
+            response
+                .headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(length));
+            response
+        }
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, header_value(content_type));
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if !headers.contains_key(CONTENT_LENGTH) {
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(len));
+    }
+    response
+}
+
+/// The inclusive byte range of a `Range: bytes=...` header for a file of
+/// `len` bytes. `None` means the header should be ignored (malformed or
+/// several ranges); `Some(None)` that the range is not satisfiable.
+fn parse_range(value: &str, len: u64) -> Option<Option<(u64, u64)>> {
+    let spec = value.strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // A suffix: the last `end` bytes.
+        let suffix = end.parse::<u64>().ok()?;
+        (suffix > 0 && len > 0).then(|| (len.saturating_sub(suffix), len - 1))
+    } else {
+        let start = start.parse::<u64>().ok()?;
+        let end = if end.is_empty() {
+            len.saturating_sub(1)
+        } else {
+            end.parse::<u64>().ok()?.min(len.saturating_sub(1))
+        };
+        (start < len && start <= end).then_some((start, end))
+    };
+    Some(range)
+}
+
+fn header_value(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+fn server_error(e: io::Error) -> Response {
+    error!("{}", e);
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+fn page_head(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>body{{font-family:sans-serif;margin:2em}}td,th{{padding:.3em .6em;text-align:left;vertical-align:top}}</style>\n\
+         </head>\n<body>\n",
+        escape(title)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_from_start_to_end() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(Some((0, 99))));
+        assert_eq!(parse_range("bytes=500-", 1000), Some(Some((500, 999))));
+        assert_eq!(parse_range("bytes= 10 - 20 ", 1000), Some(Some((10, 20))));
+    }
+
+    #[test]
+    fn range_end_is_clamped_to_file() {
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some(Some((900, 999))));
+    }
+
+    #[test]
+    fn suffix_range() {
+        assert_eq!(parse_range("bytes=-100", 1000), Some(Some((900, 999))));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some(Some((0, 999))));
+        assert_eq!(parse_range("bytes=-0", 1000), Some(None));
+        assert_eq!(parse_range("bytes=-10", 0), Some(None));
+    }
+
+    #[test]
+    fn unsatisfiable_range() {
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(None));
+        assert_eq!(parse_range("bytes=20-10", 1000), Some(None));
+        assert_eq!(parse_range("bytes=0-", 0), Some(None));
+    }
+
+    #[test]
+    fn ignored_range() {
+        assert_eq!(parse_range("items=0-10", 1000), None);
+        assert_eq!(parse_range("bytes=0-10,20-30", 1000), None);
+        assert_eq!(parse_range("bytes=a-10", 1000), None);
+        assert_eq!(parse_range("bytes=10", 1000), None);
+    }
+}
@@ -0,0 +1,430 @@
+use std::{io, time::Duration};
+
+use chrono::{DateTime, NaiveDate};
+use futures::future::BoxFuture;
+use reqwest::Client;
+use roxmltree::{Document, Node};
+use scraper::{Html, Selector};
+use serde::Deserialize;
+
+use crate::{
+    artwork,
+    config::{PodcastConfig, Quality},
+    episode::{self, Episode, Variant},
+    fetch, index,
+    selectors::Selectors,
+};
+
+const ITUNES_NS: &str = "http://www.itunes.com/dtds/podcast-1.0.dtd";
+const ATOM_NS: &str = "http://www.w3.org/2005/Atom";
+
+/// Which kind of page a series `url` points at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourceKind {
+    /// A BBC programmes episode listing, scraped for MP3 links.
+    #[default]
+    Html,
+    /// An RSS 2.0 or Atom feed with audio enclosures.
+    Feed,
+}
+
+/// One page of a source's episode list.
+#[derive(Debug, Default)]
+pub struct SourcePage {
+    pub episodes: Vec<Episode>,
+    pub has_next: bool,
+    pub series_image_url: Option<String>,
+}
+
+/// Where a series' episodes are listed.
+pub trait Source: Send + Sync {
+    /// The URL of listing page `page`, counting from 1.
+    fn page_url(&self, page: usize) -> String;
+
+    /// Reads page `page` from its body.
+    fn parse(&self, body: &str, page: usize) -> io::Result<SourcePage>;
+
+    /// Why the first page may have listed no episodes, pointing at the
+    /// setting to check.
+    fn nothing_found(&self) -> String;
+
+    fn fetch_page<'a>(
+        &'a self,
+        client: &'a Client,
+        page: usize,
+    ) -> BoxFuture<'a, io::Result<SourcePage>> {
+        Box::pin(async move {
+            let body = fetch::get_text(client, &self.page_url(page)).await?;
+            self.parse(&body, page)
+        })
+    }
+}
+
+/// The source `config` asks for.
+pub fn for_config(config: &PodcastConfig) -> Box<dyn Source> {
+    match config.source {
+        SourceKind::Html => Box::new(HtmlSource {
+            url: config.url.clone(),
+            quality: config.quality,
+            selectors: config.selectors.clone(),
+        }),
+        SourceKind::Feed => Box::new(FeedSource {
+            url: config.url.clone(),
+        }),
+    }
+}
+
+/// A BBC programmes listing, paginated with `?page=N`.
+#[derive(Debug, Clone)]
+pub struct HtmlSource {
+    url: String,
+    quality: Quality,
+    selectors: Selectors,
+}
+
+impl Source for HtmlSource {
+    fn page_url(&self, page: usize) -> String {
+        if page == 1 {
+            return self.url.clone();
+        }
+        let separator = if self.url.contains('?') { '&' } else { '?' };
+        format!("{}{}page={}", self.url, separator, page)
+    }
+
+    fn parse(&self, html: &str, page: usize) -> io::Result<SourcePage> {
+        let document = Html::parse_document(html);
+        let episodes = episode::parse_listing(&document, &self.selectors)
+            .into_iter()
+            .filter(|episode| self.quality.accepts(episode.variant))
+            .collect();
+
+        let next = Selector::parse(&self.selectors.next_page(page + 1))
+            .map_err(|_| io::Error::other("Invalid next_page selector"))?;
+
+        Ok(SourcePage {
+            episodes,
+            has_next: document.select(&next).next().is_some(),
+            series_image_url: artwork::series_image_url(&document),
+        })
+    }
+
+    fn nothing_found(&self) -> String {
+        format!(
+            "the episode selector {:?} matched no audio links of the configured quality",
+            self.selectors.episode
+        )
+    }
+}
+
+/// An RSS 2.0 or Atom feed. Feeds are a single page.
+#[derive(Debug, Clone)]
+pub struct FeedSource {
+    url: String,
+}
+
+impl Source for FeedSource {
+    fn page_url(&self, _page: usize) -> String {
+        self.url.clone()
+    }
+
+    fn parse(&self, xml: &str, _page: usize) -> io::Result<SourcePage> {
+        parse_feed(xml)
+    }
+
+    fn nothing_found(&self) -> String {
+        "the feed has no items with audio enclosures".to_string()
+    }
+}
+
+/// The episodes of an RSS 2.0 or Atom document: every item or entry with
+/// an audio enclosure.
+pub fn parse_feed(xml: &str) -> io::Result<SourcePage> {
+    let document = Document::parse(xml)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("invalid feed: {}", e)))?;
+    let root = document.root_element();
+
+    match root.tag_name().name() {
+        "rss" => {
+            let channel = child(root, None, "channel")
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "RSS without channel"))?;
+            Ok(SourcePage {
+                episodes: children(channel, None, "item")
+                    .filter_map(rss_episode)
+                    .collect(),
+                has_next: false,
+                series_image_url: child(channel, Some(ITUNES_NS), "image")
+                    .and_then(|image| image.attribute("href"))
+                    .map(str::to_string)
+                    .or_else(|| {
+                        child(channel, None, "image").and_then(|image| text(image, None, "url"))
+                    }),
+            })
+        }
+        "feed" if root.tag_name().namespace() == Some(ATOM_NS) => Ok(SourcePage {
+            episodes: children(root, Some(ATOM_NS), "entry")
+                .filter_map(atom_episode)
+                .collect(),
+            has_next: false,
+            series_image_url: text(root, Some(ATOM_NS), "logo")
+                .or_else(|| text(root, Some(ATOM_NS), "icon")),
+        }),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("not an RSS or Atom feed (root element <{}>)", other),
+        )),
+    }
+}
+
+fn rss_episode(item: Node) -> Option<Episode> {
+    let enclosure = children(item, None, "enclosure").find(|enclosure| {
+        enclosure
+            .attribute("type")
+            .is_none_or(|mime| mime.starts_with("audio/"))
+    })?;
+    let url = enclosure.attribute("url")?;
+    let guid = text(item, None, "guid");
+    let link = text(item, None, "link");
+
+    Some(Episode {
+        vpid: episode_key(url, guid.as_deref()),
+        pid: link.as_deref().and_then(episode::pid_from_page_url),
+        title: text(item, None, "title").unwrap_or_else(|| url.to_string()),
+        synopsis: text(item, None, "description")
+            .or_else(|| text(item, Some(ITUNES_NS), "summary")),
+        broadcast_date: text(item, None, "pubDate").and_then(|date| {
+            DateTime::parse_from_rfc2822(&date)
+                .ok()
+                .map(|date| date.date_naive())
+        }),
+        duration: text(item, Some(ITUNES_NS), "duration").and_then(|d| parse_clock(&d)),
+        page_url: link,
+        image_url: child(item, Some(ITUNES_NS), "image")
+            .and_then(|image| image.attribute("href"))
+            .map(str::to_string),
+        url: url.to_string(),
+        variant: Variant::High,
+    })
+}
+
+fn atom_episode(entry: Node) -> Option<Episode> {
+    let links = children(entry, Some(ATOM_NS), "link").collect::<Vec<_>>();
+    let enclosure = links.iter().find(|link| {
+        link.attribute("rel") == Some("enclosure")
+            && link
+                .attribute("type")
+                .is_none_or(|mime| mime.starts_with("audio/"))
+    })?;
+    let url = enclosure.attribute("href")?;
+    let id = text(entry, Some(ATOM_NS), "id");
+    let page_url = links
+        .iter()
+        .find(|link| link.attribute("rel").is_none_or(|rel| rel == "alternate"))
+        .and_then(|link| link.attribute("href"))
+        .map(str::to_string);
+
+    Some(Episode {
+        vpid: episode_key(url, id.as_deref()),
+        pid: page_url.as_deref().and_then(episode::pid_from_page_url),
+        title: text(entry, Some(ATOM_NS), "title").unwrap_or_else(|| url.to_string()),
+        synopsis: text(entry, Some(ATOM_NS), "summary")
+            .or_else(|| text(entry, Some(ATOM_NS), "content")),
+        broadcast_date: text(entry, Some(ATOM_NS), "published")
+            .or_else(|| text(entry, Some(ATOM_NS), "updated"))
+            .and_then(|date| parse_rfc3339_date(&date)),
+        duration: None,
+        page_url,
+        image_url: None,
+        url: url.to_string(),
+        variant: Variant::High,
+    })
+}
+
+/// The index key of a feed episode. BBC enclosures carry the same vpid
+/// as the listing pages, so both sources share one index; other feeds
+/// are keyed by a hash of the item's GUID, or of the enclosure URL.
+fn episode_key(url: &str, guid: Option<&str>) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    if path.contains("/vpid/")
+        && let Some(vpid) = index::vpid_from_url(path)
+    {
+        return vpid.to_string();
+    }
+    let id = guid.unwrap_or(url);
+    index::sha256_hex(id.as_bytes())[..16].to_string()
+}
+
+fn parse_rfc3339_date(date: &str) -> Option<NaiveDate> {
+    DateTime::parse_from_rfc3339(date)
+        .ok()
+        .map(|date| date.date_naive())
+        .or_else(|| NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok())
+}
+
+/// Parses an `itunes:duration`: seconds, `MM:SS` or `HH:MM:SS`.
+fn parse_clock(text: &str) -> Option<Duration> {
+    let mut seconds = 0;
+    for part in text.trim().split(':') {
+        seconds = seconds * 60 + part.trim().parse::<u64>().ok()?;
+    }
+    Some(Duration::from_secs(seconds))
+}
+
+fn is(node: &Node, ns: Option<&str>, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name && node.tag_name().namespace() == ns
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    ns: Option<&'a str>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(move |child| is(child, ns, name))
+}
+
+fn child<'a, 'input>(
+    node: Node<'a, 'input>,
+    ns: Option<&'a str>,
+    name: &'a str,
+) -> Option<Node<'a, 'input>> {
+    children(node, ns, name).next()
+}
+
+/// The trimmed text of the first matching child, if not empty.
+fn text(node: Node, ns: Option<&str>, name: &str) -> Option<String> {
+    let text = child(node, ns, name)?
+        .descendants()
+        .filter(Node::is_text)
+        .filter_map(|node| node.text())
+        .collect::<String>();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">
+<channel>
+  <title>6 Minute English</title>
+  <itunes:image href="https://ichef.bbci.co.uk/images/ic/3000x3000/p0lscp9t.jpg"/>
+  <item>
+    <title>Fish &amp; chips: a &lt;British&gt; classic?</title>
+    <description>Neil and Beth talk about food.</description>
+    <link>https://www.bbc.co.uk/programmes/p0l4xq77</link>
+    <guid isPermaLink="false">urn:bbc:podcast:p0l4xq77</guid>
+    <pubDate>Thu, 17 Jul 2025 03:00:00 +0000</pubDate>
+    <itunes:duration>6:10</itunes:duration>
+    <enclosure url="https://open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download/proto/https/vpid/p0lp7521.mp3" length="5923000" type="audio/mpeg"/>
+  </item>
+  <item>
+    <title>Only a transcript</title>
+    <guid>urn:bbc:podcast:p0l1abcd</guid>
+  </item>
+  <item>
+    <title>A video</title>
+    <enclosure url="https://example.com/video.mp4" type="video/mp4"/>
+  </item>
+  <item>
+    <title>Elsewhere</title>
+    <guid>https://example.com/ep-1</guid>
+    <itunes:duration>1:02:03</itunes:duration>
+    <enclosure url="https://cdn.example.com/ep1.mp3?token=abc" type="audio/mpeg"/>
+  </item>
+</channel>
+</rss>"#;
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Elsewhere</title>
+  <logo>https://example.com/logo.png</logo>
+  <entry>
+    <title>Episode two</title>
+    <link href="https://example.com/ep2"/>
+    <link rel="enclosure" type="audio/mpeg" href="https://example.com/audio/ep2.mp3"/>
+    <summary>The second one.</summary>
+    <published>2025-07-10T08:00:00+01:00</published>
+  </entry>
+  <entry>
+    <title>No audio</title>
+    <link href="https://example.com/ep3"/>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn rss_items_with_audio_enclosures() {
+        let page = parse_feed(RSS).unwrap();
+        assert_eq!(
+            page.series_image_url.as_deref(),
+            Some("https://ichef.bbci.co.uk/images/ic/3000x3000/p0lscp9t.jpg")
+        );
+        assert_eq!(page.episodes.len(), 2);
+
+        let bbc = &page.episodes[0];
+        assert_eq!(bbc.vpid, "p0lp7521");
+        assert_eq!(bbc.pid.as_deref(), Some("p0l4xq77"));
+        assert_eq!(bbc.title, "Fish & chips: a <British> classic?");
+        assert_eq!(
+            bbc.synopsis.as_deref(),
+            Some("Neil and Beth talk about food.")
+        );
+        assert_eq!(bbc.broadcast_date, NaiveDate::from_ymd_opt(2025, 7, 17));
+        assert_eq!(bbc.duration, Some(Duration::from_secs(370)));
+
+        let other = &page.episodes[1];
+        assert_eq!(other.vpid, "2babe06e0bfd4b80");
+        assert_eq!(other.pid, None);
+        assert_eq!(other.duration, Some(Duration::from_secs(3723)));
+        assert_eq!(other.url, "https://cdn.example.com/ep1.mp3?token=abc");
+    }
+
+    #[test]
+    fn atom_entries_with_enclosure_links() {
+        let page = parse_feed(ATOM).unwrap();
+        assert_eq!(
+            page.series_image_url.as_deref(),
+            Some("https://example.com/logo.png")
+        );
+        assert_eq!(page.episodes.len(), 1);
+
+        let episode = &page.episodes[0];
+        assert_eq!(episode.url, "https://example.com/audio/ep2.mp3");
+        assert_eq!(episode.vpid, "0b2b919c813993ac");
+        assert_eq!(episode.title, "Episode two");
+        assert_eq!(episode.synopsis.as_deref(), Some("The second one."));
+        assert_eq!(episode.page_url.as_deref(), Some("https://example.com/ep2"));
+        assert_eq!(episode.broadcast_date, NaiveDate::from_ymd_opt(2025, 7, 10));
+    }
+
+    #[test]
+    fn episode_keys_are_stable() {
+        assert_eq!(
+            episode_key(
+                "https://open.live.bbc.co.uk/mediaselector/6/redir/version/2.0/mediaset/audio-nondrm-download/proto/https/vpid/p0lp7521.mp3?x=1",
+                Some("urn:bbc:podcast:p0l4xq77")
+            ),
+            "p0lp7521"
+        );
+        // The same hash on every run, so re-syncing finds the same entries.
+        assert_eq!(
+            episode_key(
+                "https://cdn.example.com/ep1.mp3",
+                Some("https://example.com/ep-1")
+            ),
+            "2babe06e0bfd4b80"
+        );
+        assert_eq!(
+            episode_key("https://example.com/audio/ep2.mp3", None),
+            "0b2b919c813993ac"
+        );
+    }
+
+    #[test]
+    fn other_documents_are_rejected() {
+        let e = parse_feed("<html><body/></html>").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(parse_feed("<rss version=\"2.0\"/>").is_err());
+    }
+}
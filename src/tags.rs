@@ -0,0 +1,82 @@
+use std::{io, path::Path};
+
+use chrono::{Datelike, NaiveDate};
+use id3::{
+    Tag, TagLike, Version,
+    frame::{Comment, Picture, PictureType, Timestamp},
+};
+
+use crate::artwork::Artwork;
+
+pub const DEFAULT_ARTIST: &str = "BBC Learning English";
+const GENRE: &str = "Podcast";
+
+/// The ID3 frames we write for an episode.
+#[derive(Debug, Clone)]
+pub struct EpisodeTags<'a> {
+    pub title: &'a str,
+    pub album: &'a str,
+    pub artist: &'a str,
+    pub date: NaiveDate,
+    pub track: u32,
+    pub comment: Option<&'a str>,
+    /// Replaces the front cover when set; otherwise the existing one stays.
+    pub cover: Option<&'a Artwork>,
+}
+
+/// Whether `path` is an MP3, the only format ID3 tags are written into.
+/// Other containers would get an ID3 header put in front of their data.
+pub fn supports(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mp3"))
+}
+
+/// Writes `tags` into the MP3 at `path` as ID3v2.4, keeping any other
+/// frames the file already has.
+pub fn write(path: &Path, tags: &EpisodeTags) -> io::Result<()> {
+    let mut tag = match Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => Tag::new(),
+        Err(e) => return Err(io::Error::other(e)),
+    };
+
+    let timestamp = Timestamp {
+        year: tags.date.year(),
+        month: Some(tags.date.month() as u8),
+        day: Some(tags.date.day() as u8),
+        hour: None,
+        minute: None,
+        second: None,
+    };
+
+    tag.set_title(tags.title);
+    tag.set_album(tags.album);
+    tag.set_artist(tags.artist);
+    tag.set_album_artist(tags.artist);
+    tag.set_genre(GENRE);
+    tag.set_track(tags.track);
+    tag.set_date_released(timestamp);
+    tag.set_date_recorded(timestamp);
+
+    tag.remove_comment(None, None);
+    if let Some(comment) = tags.comment {
+        tag.add_frame(Comment {
+            lang: "eng".to_string(),
+            description: String::new(),
+            text: comment.to_string(),
+        });
+    }
+
+    if let Some(cover) = tags.cover {
+        tag.remove_picture_by_type(PictureType::CoverFront);
+        tag.add_frame(Picture {
+            mime_type: cover.mime_type.clone(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: cover.data.clone(),
+        });
+    }
+
+    tag.write_to_path(path, Version::Id3v24)
+        .map_err(io::Error::other)
+}
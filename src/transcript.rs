@@ -0,0 +1,170 @@
+use scraper::{ElementRef, Html, Node, Selector};
+
+use crate::{config::TranscriptFormat, episode};
+
+/// Folder, inside the series download folder, that holds transcripts.
+pub const TRANSCRIPT_DIR: &str = "transcripts";
+
+/// The transcript section of a BBC Learning English episode page.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    /// The page text in the configured format, vocabulary included.
+    pub content: String,
+    /// The downloadable PDF version, when the page links one.
+    pub pdf_url: Option<String>,
+}
+
+impl TranscriptFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Text => "txt",
+        }
+    }
+}
+
+/// The Learning English episode page (`.../ep-YYMMDD`) an episode's
+/// programme page links to. A Learning English URL is its own transcript
+/// page. Links to series or home pages are not followed: they would give
+/// every episode the same transcript.
+pub fn learning_english_url(page_url: &str, document: &Html) -> Option<String> {
+    if page_url.contains("/learningenglish/") {
+        return Some(page_url.to_string());
+    }
+
+    let selector = Selector::parse("a[href*=\"/learningenglish/\"][href*=\"/ep-\"]").ok()?;
+    document
+        .select(&selector)
+        .find_map(|a| a.value().attr("href"))
+        .map(absolute)
+}
+
+/// The rich-text body of a Learning English page and its PDF link, or
+/// `None` when the page has no body text.
+pub fn parse(document: &Html, format: TranscriptFormat) -> Option<Transcript> {
+    let body = Selector::parse(".widget-richtext").ok()?;
+    let pdf = Selector::parse("a[href$=\".pdf\"]").ok()?;
+
+    let mut content = String::new();
+    for element in document.select(&body) {
+        render(element, format, &mut content);
+        content.push_str("\n\n");
+    }
+
+    let content = tidy(&content);
+    if content.is_empty() {
+        return None;
+    }
+    Some(Transcript {
+        content,
+        pdf_url: document
+            .select(&pdf)
+            .find_map(|a| a.value().attr("href"))
+            .map(absolute),
+    })
+}
+
+/// Appends the text of `element` to `out`, keeping headings, paragraphs,
+/// line breaks, list items and (for Markdown) emphasis.
+fn render(element: ElementRef, format: TranscriptFormat, out: &mut String) {
+    let markdown = format == TranscriptFormat::Markdown;
+
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => push_text(out, text),
+            Node::Element(_) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                match child.value().name() {
+                    "script" | "style" | "noscript" | "img" | "figure" | "button" => {}
+                    "br" => out.push('\n'),
+                    name @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+                        out.push_str("\n\n");
+                        if markdown {
+                            let level = name[1..].parse::<usize>().unwrap_or(2);
+                            out.push_str(&"#".repeat(level));
+                            out.push(' ');
+                        }
+                        render(child, format, out);
+                        out.push_str("\n\n");
+                    }
+                    "li" => {
+                        out.push_str("\n- ");
+                        render(child, format, out);
+                        out.push('\n');
+                    }
+                    "p" | "div" | "section" | "ul" | "ol" | "blockquote" | "table" | "tr" => {
+                        out.push_str("\n\n");
+                        render(child, format, out);
+                        out.push_str("\n\n");
+                    }
+                    "strong" | "b" if markdown => emphasise(child, format, "**", out),
+                    "em" | "i" if markdown => emphasise(child, format, "_", out),
+                    _ => render(child, format, out),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Renders `element` wrapped in `marker`, keeping the marker next to the
+/// text and any surrounding spaces outside it.
+fn emphasise(element: ElementRef, format: TranscriptFormat, marker: &str, out: &mut String) {
+    let mut inner = String::new();
+    render(element, format, &mut inner);
+    let trimmed = inner.trim();
+    if trimmed.is_empty() || trimmed.contains('\n') {
+        out.push_str(&inner);
+        return;
+    }
+    if inner.starts_with(char::is_whitespace) {
+        out.push(' ');
+    }
+    out.push_str(marker);
+    out.push_str(trimmed);
+    out.push_str(marker);
+    if inner.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+}
+
+/// Appends `text` with every run of whitespace collapsed to one space.
+fn push_text(out: &mut String, text: &str) {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.starts_with(char::is_whitespace) && !out.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+    out.push_str(&collapsed);
+    if !collapsed.is_empty() && text.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+}
+
+/// Trims every line and leaves at most one blank line between blocks.
+fn tidy(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank = false;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            blank = !out.is_empty();
+            continue;
+        }
+        if blank {
+            out.push('\n');
+            blank = false;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+fn absolute(url: &str) -> String {
+    if url.starts_with('/') && !url.starts_with("//") {
+        format!("https://www.bbc.co.uk{}", url)
+    } else {
+        episode::absolute(url)
+    }
+}
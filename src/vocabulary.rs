@@ -0,0 +1,239 @@
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+/// A term from the "Vocabulary" section of an episode's transcript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VocabularyEntry {
+    pub term: String,
+    pub definition: String,
+    pub episode_pid: String,
+}
+
+/// The terms listed under the "Vocabulary" heading of a saved transcript,
+/// Markdown or text. Each term is a paragraph whose first line is the
+/// term and whose remaining lines are its definition; the section ends at
+/// the next single-line paragraph, which is the next heading.
+pub fn parse(transcript: &str, episode_pid: &str) -> Vec<VocabularyEntry> {
+    let mut paragraphs = paragraphs(transcript);
+    if !paragraphs
+        .by_ref()
+        .any(|lines| lines.len() == 1 && is_vocabulary_heading(lines[0]))
+    {
+        return Vec::new();
+    }
+
+    paragraphs
+        .take_while(|lines| lines.len() > 1 && !lines[0].starts_with('#'))
+        .map(|lines| VocabularyEntry {
+            term: plain(lines[0]),
+            definition: lines[1..]
+                .iter()
+                .map(|line| plain(line))
+                .collect::<Vec<_>>()
+                .join(" "),
+            episode_pid: episode_pid.to_string(),
+        })
+        .filter(|entry| !entry.term.is_empty() && !entry.definition.is_empty())
+        .collect()
+}
+
+/// The first sentence of the transcript, outside the "Vocabulary"
+/// section, that uses `term`.
+pub fn example_sentence(transcript: &str, term: &str) -> Option<String> {
+    // Definitions often add what the term applies to, as in
+    // "get to the bottom of (something)".
+    let term = term.split('(').next().unwrap_or(term).trim().to_lowercase();
+    if term.is_empty() {
+        return None;
+    }
+
+    let mut in_vocabulary = false;
+    for lines in paragraphs(transcript) {
+        if lines.len() == 1 && is_vocabulary_heading(lines[0]) {
+            in_vocabulary = true;
+            continue;
+        }
+        if in_vocabulary {
+            if lines.len() > 1 && !lines[0].starts_with('#') {
+                continue;
+            }
+            in_vocabulary = false;
+        }
+        if lines[0].starts_with('#') {
+            continue;
+        }
+
+        for line in lines {
+            let line = plain(line);
+            if let Some(sentence) = sentences(&line).find(|sentence| uses(sentence, &term)) {
+                return Some(sentence.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// The non-empty paragraphs of `text`, each as its trimmed lines.
+fn paragraphs(text: &str) -> impl Iterator<Item = Vec<&str>> {
+    text.split("\n\n")
+        .map(|paragraph| paragraph.trim().lines().map(str::trim).collect::<Vec<_>>())
+        .filter(|lines| !lines.is_empty())
+}
+
+/// The sentences of `line`, each keeping its closing punctuation.
+fn sentences(line: &str) -> impl Iterator<Item = &str> {
+    line.split_inclusive(['.', '?', '!'])
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+}
+
+/// Whether `sentence` contains `term` (lowercase) as whole words.
+fn uses(sentence: &str, term: &str) -> bool {
+    let sentence = sentence.to_lowercase();
+    let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    sentence.match_indices(term).any(|(start, _)| {
+        !is_word(sentence[..start].chars().next_back())
+            && !is_word(sentence[start + term.len()..].chars().next())
+    })
+}
+
+fn is_vocabulary_heading(line: &str) -> bool {
+    plain(line.trim_start_matches('#')).eq_ignore_ascii_case("vocabulary")
+}
+
+/// `line` without Markdown emphasis.
+fn plain(line: &str) -> String {
+    let line = line.replace("**", "");
+    let line = line.trim();
+    line.strip_prefix('_')
+        .and_then(|line| line.strip_suffix('_'))
+        .unwrap_or(line)
+        .trim()
+        .to_string()
+}
+
+pub fn write_json(out: &mut impl Write, entries: &[VocabularyEntry]) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, entries)?;
+    writeln!(out)
+}
+
+/// RFC 4180 CSV with a header row.
+pub fn write_csv(out: &mut impl Write, entries: &[VocabularyEntry]) -> io::Result<()> {
+    write!(out, "term,definition,episode_pid\r\n")?;
+    for entry in entries {
+        write!(
+            out,
+            "{},{},{}\r\n",
+            csv_field(&entry.term),
+            csv_field(&entry.definition),
+            csv_field(&entry.episode_pid)
+        )?;
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+
+    use super::*;
+    use crate::{config::TranscriptFormat, transcript};
+
+    /// The body of a 6 Minute English page, trimmed to a few paragraphs
+    /// per section.
+    const PAGE: &str = r#"<html><body>
+<div class="widget widget-richtext 6"><div class="text" dir="ltr">
+<h3>Introduction</h3>
+<p>Can forensic science really catch criminals? Neil and Beth discuss <em>crime</em> and learn some useful vocabulary.</p>
+<h3>This week's question</h3>
+<p>When was fingerprinting first used to solve a murder?</p>
+<h3>Vocabulary</h3>
+<p><strong>forensic</strong><br />relating to scientific methods of solving crimes</p>
+<p><strong>hunch</strong><br />feeling that something is true<br />without having evidence</p>
+<p><strong>get to the bottom of (something)</strong><br />find out the real cause of a problem</p>
+<h3>TRANSCRIPT</h3>
+<p><strong>Note: This is not a word-for-word transcript</strong></p>
+<p><strong>Neil</strong><br />Hello. This is 6 Minute English from BBC Learning English. I'm Neil.</p>
+<p><strong>Beth</strong><br />And I'm Beth. Detectives often follow a hunch. Forensic scientists want to get to the bottom of it!</p>
+</div></div></body></html>"#;
+
+    fn saved(format: TranscriptFormat) -> String {
+        transcript::parse(&Html::parse_document(PAGE), format)
+            .unwrap()
+            .content
+    }
+
+    fn terms(entries: &[VocabularyEntry]) -> Vec<(&str, &str)> {
+        entries
+            .iter()
+            .map(|entry| (entry.term.as_str(), entry.definition.as_str()))
+            .collect()
+    }
+
+    const EXPECTED: &[(&str, &str)] = &[
+        (
+            "forensic",
+            "relating to scientific methods of solving crimes",
+        ),
+        (
+            "hunch",
+            "feeling that something is true without having evidence",
+        ),
+        (
+            "get to the bottom of (something)",
+            "find out the real cause of a problem",
+        ),
+    ];
+
+    #[test]
+    fn detects_vocabulary_headings() {
+        assert!(is_vocabulary_heading("Vocabulary"));
+        assert!(is_vocabulary_heading("### Vocabulary"));
+        assert!(is_vocabulary_heading("**VOCABULARY**"));
+        assert!(is_vocabulary_heading("_vocabulary_"));
+        assert!(!is_vocabulary_heading("Vocabulary list"));
+        assert!(!is_vocabulary_heading("### TRANSCRIPT"));
+    }
+
+    #[test]
+    fn parses_markdown_transcript() {
+        let entries = parse(&saved(TranscriptFormat::Markdown), "p0l4xq77");
+        assert_eq!(terms(&entries), EXPECTED);
+        assert!(entries.iter().all(|entry| entry.episode_pid == "p0l4xq77"));
+    }
+
+    #[test]
+    fn parses_text_transcript() {
+        let entries = parse(&saved(TranscriptFormat::Text), "p0l4xq77");
+        assert_eq!(terms(&entries), EXPECTED);
+    }
+
+    #[test]
+    fn no_vocabulary_section() {
+        let transcript =
+            "### Introduction\n\nNo terms this week.\n\n### TRANSCRIPT\n\n**Neil**\nHello.";
+        assert!(parse(transcript, "p0l4xq77").is_empty());
+    }
+
+    #[test]
+    fn example_sentence_skips_vocabulary_section() {
+        let transcript = saved(TranscriptFormat::Markdown);
+        assert_eq!(
+            example_sentence(&transcript, "hunch").as_deref(),
+            Some("Detectives often follow a hunch.")
+        );
+        assert_eq!(
+            example_sentence(&transcript, "get to the bottom of (something)").as_deref(),
+            Some("Forensic scientists want to get to the bottom of it!")
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::{io, sync::Arc};
+
+use chrono::Local;
+use tokio::{task::JoinSet, time};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{Limits, PodcastConfig},
+    downloader::{PodcastDownloader, SyncOptions},
+    fetch::Validators,
+    limiter::Limiter,
+    progress::Progress,
+    retry::ErrorClass,
+};
+
+/// Syncs every series on its own `schedule` until SIGTERM or Ctrl-C, all
+/// of them sharing the slots of `limits`. A series is checked again only
+/// once its previous check is over. On the first signal the running
+/// checks are finished; a second one stops at once.
+pub async fn run(
+    podcasts: Vec<PodcastConfig>,
+    limits: &Limits,
+    retry_failed: bool,
+    progress: Progress,
+) -> io::Result<()> {
+    let limiter = Arc::new(Limiter::new(limits));
+    let stop = CancellationToken::new();
+    let mut tasks = JoinSet::new();
+    for podcast in podcasts {
+        info!("Watching {} {}", podcast.name, podcast.schedule);
+        tasks.spawn(watch(
+            podcast,
+            Arc::clone(&limiter),
+            progress.clone(),
+            stop.clone(),
+            retry_failed,
+        ));
+    }
+
+    tokio::select! {
+        _ = shutdown() => {
+            info!("Stopping once the running checks are over");
+            stop.cancel();
+        }
+        _ = wait(&mut tasks) => return Ok(()),
+    }
+    tokio::select! {
+        _ = wait(&mut tasks) => {}
+        _ = shutdown() => {
+            warn!("Stopping now");
+            tasks.abort_all();
+        }
+    }
+    Ok(())
+}
+
+/// Checks `podcast` on its schedule until `stop` is cancelled or the
+/// schedule runs out.
+async fn watch(
+    podcast: PodcastConfig,
+    limiter: Arc<Limiter>,
+    progress: Progress,
+    stop: CancellationToken,
+    retry_failed: bool,
+) {
+    let mut validators = Validators::default();
+    loop {
+        let started = Local::now();
+        match check(&podcast, &limiter, &progress, &validators, retry_failed).await {
+            Ok(Some(changed)) => validators = changed,
+            Ok(None) => {}
+            Err(e) => error!("Error checking {}: {}", podcast.name, e),
+        }
+        if stop.is_cancelled() {
+            return;
+        }
+
+        let Some(next) = podcast.schedule.next(started, Local::now()) else {
+            warn!("No more checks of {} scheduled", podcast.name);
+            return;
+        };
+        info!(
+            "Next check of {} at {}",
+            podcast.name,
+            next.format("%Y-%m-%d %H:%M:%S")
+        );
+        let delay = (next - Local::now()).to_std().unwrap_or_default();
+        tokio::select! {
+            biased;
+            _ = stop.cancelled() => return,
+            _ = time::sleep(delay) => {}
+        }
+    }
+}
+
+/// Syncs `podcast` if its first listing page changed since the response
+/// `validators` came from. Returns the page's new validators unless a
+/// download failed for a reason that may pass, so that those are tried
+/// again on the next check even if the page stays the same.
+async fn check(
+    podcast: &PodcastConfig,
+    limiter: &Arc<Limiter>,
+    progress: &Progress,
+    validators: &Validators,
+    retry_failed: bool,
+) -> io::Result<Option<Validators>> {
+    let downloader = PodcastDownloader::with_limiter(podcast.clone(), Arc::clone(limiter))?
+        .with_progress(progress.clone());
+    let Some(changed) = downloader.listing_if_modified(validators).await? else {
+        info!("{} has not changed", podcast.name);
+        return Ok(None);
+    };
+    let options = SyncOptions {
+        retry_failed,
+        walk_all: false,
+    };
+    let summary = downloader.download_episodes(options).await?;
+    let transient = summary
+        .downloads
+        .iter()
+        .any(|download| download.error_class == Some(ErrorClass::Transient));
+    Ok((!transient).then_some(changed))
+}
+
+/// Waits for every task; a panicking one is logged.
+async fn wait(tasks: &mut JoinSet<()>) {
+    while let Some(joined) = tasks.join_next().await {
+        if let Err(e) = joined {
+            error!("Watcher failed: {}", e);
+        }
+    }
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM where there is one.
+async fn shutdown() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}